@@ -0,0 +1,77 @@
+// Generates the embedded asset table for the `embed-assets` feature: every
+// file under `static/` (except runtime uploads) and every `.tera` template
+// under `templates/`, compiled into the binary with `include_bytes!`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    if env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_none() {
+        return;
+    }
+    println!("cargo:rerun-if-changed=static");
+    println!("cargo:rerun-if-changed=templates");
+
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let mut static_files = Vec::new();
+    collect(
+        &root.join("static"),
+        &root.join("static"),
+        &mut static_files,
+    );
+    static_files.retain(|(name, _)| !name.starts_with("uploads/"));
+    static_files.sort();
+
+    let mut templates = Vec::new();
+    collect(
+        &root.join("templates"),
+        &root.join("templates"),
+        &mut templates,
+    );
+    templates.retain(|(name, _)| name.ends_with(".tera"));
+    templates.sort();
+
+    let built_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+
+    let mut out = String::new();
+    out.push_str(&format!("pub const BUILT_AT: u64 = {};\n", built_at));
+    out.push_str("pub static STATIC_FILES: &[(&str, &[u8])] = &[\n");
+    for (name, path) in &static_files {
+        out.push_str(&format!("    ({:?}, include_bytes!({:?})),\n", name, path));
+    }
+    out.push_str("];\npub static TEMPLATES: &[(&str, &str)] = &[\n");
+    for (name, path) in &templates {
+        out.push_str(&format!("    ({:?}, include_str!({:?})),\n", name, path));
+    }
+    out.push_str("];\n");
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("embedded_assets.rs"), out).unwrap();
+}
+
+/// `(path relative to root with '/' separators, absolute path)` for every file
+fn collect(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect(root, &path, files);
+        } else if path.is_file() {
+            let name = path
+                .strip_prefix(root)
+                .unwrap()
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((name, path));
+        }
+    }
+}
@@ -1,5 +1,11 @@
 use std::env;
 
+/// Manifest entries and static files every page needs early, plus the 3D
+/// scene on the home page
+const DEFAULT_PRELOAD_ROUTES: &str =
+    "/=css/style.css,js/main.js,js/three-scene.js,fonts/inter.css,fonts/inter-variable.woff2;\
+     *=css/style.css,fonts/inter.css,fonts/inter-variable.woff2";
+
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub host: String,
@@ -8,6 +14,53 @@ pub struct AppConfig {
     pub templates_dir: String,
     pub database_url: Option<String>,
     pub environment: Environment,
+    pub site_url: String,
+    pub admin_token: Option<Secret>,
+    pub preview_secret: Secret,
+    /// Renamed project pages as `(old slug, current slug)` pairs
+    pub project_redirects: Vec<(String, String)>,
+    /// Revisions kept per blog post
+    pub revision_limit: usize,
+    /// Revisions older than this are pruned (the newest is always kept)
+    pub revision_max_age_days: Option<i64>,
+    /// Directory for rendered Open Graph cards
+    pub og_cache_dir: String,
+    /// Memory budget for cached static files (0 disables the cache)
+    pub static_cache_max_bytes: u64,
+    /// Larger static files are always streamed from disk
+    pub static_cache_max_file_bytes: u64,
+    /// Hidden static paths that may still be served (e.g. `.well-known`)
+    pub static_hidden_allowlist: Vec<String>,
+    /// `Cache-Control` overrides for static paths as `(pattern, value)`
+    pub static_cache_control: Vec<(String, String)>,
+    /// Vite dev server (e.g. `http://localhost:5173`), used in development
+    pub vite_dev_url: Option<String>,
+    /// Critical assets per route pattern, sent as `Link` preload headers
+    pub preload_routes: Vec<(String, Vec<String>)>,
+    /// Memory budget for rendered HTML pages (0 disables the page cache)
+    pub page_cache_max_bytes: u64,
+    /// Locales pages can be rendered in, the default first
+    pub site_locales: Vec<String>,
+}
+
+/// Sensitive configuration value that never shows up in `Debug` output
+#[derive(Clone, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[redacted]")
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -31,6 +84,66 @@ impl AppConfig {
                 Ok("production") => Environment::Production,
                 _ => Environment::Development,
             },
+            site_url: env::var("SITE_URL")
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|_| "https://dav88.dev".to_string()),
+            admin_token: env::var("ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty())
+                .map(Secret::new),
+            preview_secret: Secret::new(env::var("PREVIEW_SECRET").unwrap_or_else(|_| {
+                // Per-process secret: preview links stop working after a restart
+                format!(
+                    "{}{}",
+                    uuid::Uuid::new_v4().simple(),
+                    uuid::Uuid::new_v4().simple()
+                )
+            })),
+            project_redirects: env::var("PROJECT_REDIRECTS")
+                .map(|value| parse_redirect_pairs(&value))
+                .unwrap_or_default(),
+            revision_limit: env::var("REVISION_LIMIT")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(50),
+            revision_max_age_days: env::var("REVISION_MAX_AGE_DAYS")
+                .ok()
+                .and_then(|value| value.parse().ok()),
+            og_cache_dir: env::var("OG_CACHE_DIR").unwrap_or_else(|_| "cache/og".to_string()),
+            static_cache_max_bytes: env::var("STATIC_CACHE_MAX_BYTES")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(64 * 1024 * 1024),
+            static_cache_max_file_bytes: env::var("STATIC_CACHE_MAX_FILE_BYTES")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(2 * 1024 * 1024),
+            static_hidden_allowlist: env::var("STATIC_HIDDEN_ALLOWLIST")
+                .unwrap_or_else(|_| ".well-known".to_string())
+                .split(',')
+                .map(|path| path.trim().to_string())
+                .filter(|path| !path.is_empty())
+                .collect(),
+            static_cache_control: env::var("STATIC_CACHE_CONTROL")
+                .map(|value| parse_cache_control_overrides(&value))
+                .unwrap_or_default(),
+            vite_dev_url: env::var("VITE_DEV_URL")
+                .ok()
+                .map(|url| url.trim_end_matches('/').to_string())
+                .filter(|url| !url.is_empty()),
+            preload_routes: parse_preload_routes(
+                &env::var("PRELOAD_ROUTES").unwrap_or_else(|_| DEFAULT_PRELOAD_ROUTES.to_string()),
+            ),
+            page_cache_max_bytes: env::var("PAGE_CACHE_MAX_BYTES")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(16 * 1024 * 1024),
+            site_locales: env::var("SITE_LOCALES")
+                .unwrap_or_else(|_| "en".to_string())
+                .split(',')
+                .map(|locale| locale.trim().to_string())
+                .filter(|locale| !locale.is_empty())
+                .collect(),
         }
     }
 
@@ -38,7 +151,53 @@ impl AppConfig {
         self.environment == Environment::Development
     }
 
+    /// The Vite dev server to serve frontend modules from, in development only
+    pub fn vite_dev_server(&self) -> Option<&str> {
+        self.vite_dev_url
+            .as_deref()
+            .filter(|_| self.is_development())
+    }
+
     pub fn bind_address(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
 }
+
+/// Parse `old=new,old2=new2` into slug pairs, skipping malformed entries
+fn parse_redirect_pairs(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(old, new)| (old.trim().to_string(), new.trim().to_string()))
+        .filter(|(old, new)| !old.is_empty() && !new.is_empty())
+        .collect()
+}
+
+/// Parse `pattern=Cache-Control;pattern2=...` (values contain commas),
+/// skipping malformed entries
+fn parse_cache_control_overrides(value: &str) -> Vec<(String, String)> {
+    value
+        .split(';')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(pattern, value)| (pattern.trim().to_string(), value.trim().to_string()))
+        .filter(|(pattern, value)| !pattern.is_empty() && !value.is_empty())
+        .collect()
+}
+
+/// Parse `route=asset,asset;route2=asset` into route patterns and their
+/// critical assets, skipping malformed entries
+fn parse_preload_routes(value: &str) -> Vec<(String, Vec<String>)> {
+    value
+        .split(';')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(route, assets)| {
+            let assets = assets
+                .split(',')
+                .map(|asset| asset.trim().to_string())
+                .filter(|asset| !asset.is_empty())
+                .collect();
+            (route.trim().to_string(), assets)
+        })
+        .filter(|(route, assets): &(String, Vec<String>)| !route.is_empty() && !assets.is_empty())
+        .collect()
+}
@@ -0,0 +1,246 @@
+use crate::config::AppConfig;
+use crate::models::CVData;
+use crate::services::revisions::diff_between;
+use crate::services::session::{SessionSigner, SESSION_TTL};
+use crate::services::{AssetPaths, BlogRepository, InMemoryBlogRepository};
+use axum::{
+    async_trait,
+    extract::{Extension, Form, FromRequestParts, Path, Query},
+    http::{header, request::Parts, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use std::sync::Arc;
+use tera::{Context, Tera};
+use uuid::Uuid;
+
+/// Cookie carrying the signed session for the browser admin area
+const ADMIN_COOKIE: &str = "admin_session";
+
+/// Extractor guarding admin endpoints with the `ADMIN_TOKEN` bearer token,
+/// or the signed session cookie set by the admin login.
+/// Admin routes behave as missing when no token is configured.
+pub struct AdminAuth;
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AdminAuth {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let config = parts
+            .extensions
+            .get::<Arc<AppConfig>>()
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        let Some(expected) = &config.admin_token else {
+            return Err(StatusCode::NOT_FOUND);
+        };
+
+        let bearer = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if let Some(provided) = bearer {
+            if constant_time_eq(provided.as_bytes(), expected.expose().as_bytes()) {
+                return Ok(AdminAuth);
+            }
+            tracing::warn!("Rejected admin request with invalid token");
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        let session = admin_cookie(parts).ok_or(StatusCode::UNAUTHORIZED)?;
+        match SessionSigner::new(expected.expose()).verify(session, Utc::now()) {
+            Ok(()) => Ok(AdminAuth),
+            Err(err) => {
+                tracing::warn!("Rejected admin request: {}", err);
+                Err(StatusCode::UNAUTHORIZED)
+            }
+        }
+    }
+}
+
+fn admin_cookie(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == ADMIN_COOKIE)
+        .map(|(_, value)| value)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// `AdminAuth` for browser pages: unauthenticated visitors are sent to the
+/// login form instead of getting a bare 401
+pub struct AdminSession;
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AdminSession {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match AdminAuth::from_request_parts(parts, state).await {
+            Ok(_) => Ok(AdminSession),
+            Err(StatusCode::UNAUTHORIZED) => Err(Redirect::to("/admin/login").into_response()),
+            Err(status) => Err(status.into_response()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginForm {
+    pub token: String,
+}
+
+pub async fn login_page(
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+) -> Response {
+    if config.admin_token.is_none() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    render_login(&templates, &cv_data, &asset_paths, None)
+}
+
+pub async fn login(
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+    Form(form): Form<LoginForm>,
+) -> Response {
+    let Some(expected) = &config.admin_token else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if !constant_time_eq(form.token.as_bytes(), expected.expose().as_bytes()) {
+        tracing::warn!("Rejected admin login with invalid token");
+        let mut response = render_login(&templates, &cv_data, &asset_paths, Some("Invalid token"));
+        *response.status_mut() = StatusCode::UNAUTHORIZED;
+        return response;
+    }
+
+    let secure = if config.is_development() {
+        ""
+    } else {
+        "; Secure"
+    };
+    let session = SessionSigner::new(expected.expose()).sign(Utc::now() + SESSION_TTL);
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}{}",
+        ADMIN_COOKIE,
+        session,
+        SESSION_TTL.num_seconds(),
+        secure
+    );
+    ([(header::SET_COOKIE, cookie)], Redirect::to("/admin")).into_response()
+}
+
+pub async fn logout() -> Response {
+    let cookie = format!(
+        "{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0",
+        ADMIN_COOKIE
+    );
+    ([(header::SET_COOKIE, cookie)], Redirect::to("/admin/login")).into_response()
+}
+
+/// Every post, including drafts and scheduled ones
+pub async fn admin_index(
+    _session: AdminSession,
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+) -> Result<Response, StatusCode> {
+    let posts = repo.get_all_posts().await.map_err(internal_error)?;
+
+    let mut context = Context::new();
+    context.insert("cv_data", cv_data.as_ref());
+    context.insert("assets", asset_paths.as_ref());
+    context.insert("posts", &posts);
+
+    render(&templates, "admin/index.html.tera", &context)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DiffQuery {
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+}
+
+pub async fn post_revisions(
+    _session: AdminSession,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DiffQuery>,
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+) -> Result<Response, StatusCode> {
+    let post = repo
+        .get_post_by_id(id)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let revisions = repo.list_revisions(id).await.map_err(internal_error)?;
+    let diff = diff_between(&revisions, query.from, query.to);
+
+    let mut context = Context::new();
+    context.insert("cv_data", cv_data.as_ref());
+    context.insert("assets", asset_paths.as_ref());
+    context.insert("post", &post);
+    context.insert("revisions", &revisions);
+    context.insert("diff", &diff);
+
+    render(&templates, "admin/revisions.html.tera", &context)
+}
+
+pub async fn rollback_revision(
+    _session: AdminSession,
+    Path((id, number)): Path<(Uuid, u32)>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+) -> Result<Response, StatusCode> {
+    repo.rollback_post(id, number, None)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Redirect::to(&format!("/admin/posts/{}/revisions", id)).into_response())
+}
+
+fn render_login(
+    templates: &Tera,
+    cv_data: &CVData,
+    asset_paths: &AssetPaths,
+    error: Option<&str>,
+) -> Response {
+    let mut context = Context::new();
+    context.insert("cv_data", cv_data);
+    context.insert("assets", asset_paths);
+    context.insert("error", &error);
+
+    render(templates, "admin/login.html.tera", &context).unwrap_or_else(IntoResponse::into_response)
+}
+
+fn render(templates: &Tera, name: &str, context: &Context) -> Result<Response, StatusCode> {
+    templates
+        .render(name, context)
+        .map(|html| ([(header::CACHE_CONTROL, "private, no-store")], Html(html)).into_response())
+        .map_err(|err| {
+            tracing::error!("Template rendering error: {}", err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+fn internal_error(err: anyhow::Error) -> StatusCode {
+    tracing::error!("Blog repository error: {}", err);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
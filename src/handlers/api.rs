@@ -1,6 +1,21 @@
-use crate::models::{ApiResponse, CVData};
-use axum::{extract::Extension, http::StatusCode, response::Json};
+use crate::config::AppConfig;
+use crate::handlers::admin::{AdminAuth, DiffQuery};
+use crate::models::{ApiResponse, BlogPost, BlogRevision, CVData, CreateBlogPost};
+use crate::services::images::ImageError;
+use crate::services::preview::{PreviewSigner, DEFAULT_PREVIEW_TTL};
+use crate::services::revisions::diff_between;
+use crate::services::{
+    BlogRepository, ImageStore, InMemoryBlogRepository, RevisionDiff, StoredImage,
+};
+use axum::{
+    extract::{Extension, Multipart, Path, Query},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use uuid::Uuid;
 
 pub async fn get_cv_data(
     Extension(cv_data): Extension<Arc<CVData>>,
@@ -14,17 +29,197 @@ pub async fn get_cv_json(
     Ok(Json(cv_data.as_ref().clone()))
 }
 
-// Future blog endpoints (ready for implementation)
-/*
-pub async fn get_blog_posts() -> Result<Json<ApiResponse<Vec<BlogPost>>>, StatusCode> {
-    // TODO: Implement with database
-    Ok(Json(ApiResponse::success(vec![])))
+pub async fn get_blog_posts(
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+) -> Result<Json<ApiResponse<Vec<BlogPost>>>, StatusCode> {
+    let posts = repo.get_published_posts().await.map_err(internal_error)?;
+    Ok(Json(ApiResponse::success(posts)))
 }
 
 pub async fn create_blog_post(
+    _admin: AdminAuth,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
     Json(payload): Json<CreateBlogPost>,
 ) -> Result<Json<ApiResponse<BlogPost>>, StatusCode> {
-    // TODO: Implement with database
-    Err(StatusCode::NOT_IMPLEMENTED)
+    let post = repo.create_post(payload).await.map_err(internal_error)?;
+    Ok(Json(ApiResponse::success(post)))
+}
+
+pub async fn update_blog_post(
+    _admin: AdminAuth,
+    Path(id): Path<Uuid>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Json(payload): Json<CreateBlogPost>,
+) -> Result<Json<ApiResponse<BlogPost>>, StatusCode> {
+    let post = repo
+        .update_post(id, payload)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(ApiResponse::success(post)))
+}
+
+pub async fn delete_blog_post(
+    _admin: AdminAuth,
+    Path(id): Path<Uuid>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+) -> Result<Json<ApiResponse<bool>>, StatusCode> {
+    if repo.delete_post(id).await.map_err(internal_error)? {
+        Ok(Json(ApiResponse::success(true)))
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PreviewLinkRequest {
+    pub ttl_hours: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreviewLink {
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Mint a signed, expiring link to view a post before it is published
+pub async fn create_preview_link(
+    _admin: AdminAuth,
+    Path(id): Path<Uuid>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(signer): Extension<Arc<PreviewSigner>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    payload: Option<Json<PreviewLinkRequest>>,
+) -> Result<Json<ApiResponse<PreviewLink>>, StatusCode> {
+    repo.get_post_by_id(id)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let ttl = payload
+        .and_then(|Json(request)| request.ttl_hours)
+        .map(|hours| Duration::hours(hours.clamp(1, 24 * 30)))
+        .unwrap_or(DEFAULT_PREVIEW_TTL);
+    let expires_at = Utc::now() + ttl;
+    let token = signer.sign(id, expires_at);
+
+    Ok(Json(ApiResponse::success(PreviewLink {
+        url: format!("{}/blog/preview/{}", config.site_url, token),
+        expires_at,
+    })))
+}
+
+pub async fn list_revisions(
+    _admin: AdminAuth,
+    Path(id): Path<Uuid>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+) -> Result<Json<ApiResponse<Vec<BlogRevision>>>, StatusCode> {
+    repo.get_post_by_id(id)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let revisions = repo.list_revisions(id).await.map_err(internal_error)?;
+    Ok(Json(ApiResponse::success(revisions)))
+}
+
+pub async fn get_revision(
+    _admin: AdminAuth,
+    Path((id, number)): Path<(Uuid, u32)>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+) -> Result<Json<ApiResponse<BlogRevision>>, StatusCode> {
+    let revision = repo
+        .get_revision(id, number)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(ApiResponse::success(revision)))
+}
+
+/// Line diff between two revisions, `?from=&to=` defaulting to the latest change
+pub async fn diff_revisions(
+    _admin: AdminAuth,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DiffQuery>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+) -> Result<Json<ApiResponse<RevisionDiff>>, StatusCode> {
+    let revisions = repo.list_revisions(id).await.map_err(internal_error)?;
+    let diff = diff_between(&revisions, query.from, query.to).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(ApiResponse::success(diff)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RollbackRequest {
+    pub author: Option<String>,
+}
+
+pub async fn rollback_revision(
+    _admin: AdminAuth,
+    Path((id, number)): Path<(Uuid, u32)>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    payload: Option<Json<RollbackRequest>>,
+) -> Result<Json<ApiResponse<BlogPost>>, StatusCode> {
+    let author = payload.and_then(|Json(request)| request.author);
+    let post = repo
+        .rollback_post(id, number, author)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(ApiResponse::success(post)))
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadedImage {
+    #[serde(flatten)]
+    pub image: StoredImage,
+    pub url: String,
+    /// Snippet to paste into a post; rendered as a responsive `<picture>`
+    pub markdown: String,
+}
+
+/// Accept an image as the `file` field of a multipart form
+pub async fn upload_image(
+    _admin: AdminAuth,
+    Extension(images): Extension<Arc<ImageStore>>,
+    mut multipart: Multipart,
+) -> Result<Json<ApiResponse<UploadedImage>>, StatusCode> {
+    let mut bytes = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+    {
+        if field.name() == Some("file") {
+            bytes = Some(field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+            break;
+        }
+    }
+    let bytes = bytes.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let image = images
+        .store(bytes.to_vec())
+        .await
+        .map_err(|err| match err {
+            ImageError::UnsupportedFormat => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ImageError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ImageError::Decode(reason) => {
+                tracing::warn!("Rejected undecodable upload: {}", reason);
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ImageError::Storage(err) => {
+                tracing::error!("Failed to store upload: {:#}", err);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    let url = image.fallback_url();
+    Ok(Json(ApiResponse::success(UploadedImage {
+        markdown: format!("![]({})", url),
+        url,
+        image,
+    })))
+}
+
+fn internal_error(err: anyhow::Error) -> StatusCode {
+    tracing::error!("Blog repository error: {}", err);
+    StatusCode::INTERNAL_SERVER_ERROR
 }
-*/
@@ -0,0 +1,232 @@
+use crate::models::CVData;
+use crate::services::preview::PreviewSigner;
+use crate::services::{
+    AssetPaths, BlogRepository, InMemoryBlogRepository, MarkdownRenderer, SocialCards,
+};
+use crate::utils::slugify;
+use axum::{
+    extract::{Extension, Path},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use chrono::Utc;
+use std::sync::Arc;
+use tera::{Context, Tera};
+
+pub async fn blog_index(
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+) -> Result<Html<String>, StatusCode> {
+    let posts = repo.get_published_posts().await.map_err(|err| {
+        tracing::error!("Failed to load posts: {}", err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let mut context = Context::new();
+    context.insert("cv_data", cv_data.as_ref());
+    context.insert("assets", asset_paths.as_ref());
+    context.insert("posts", &posts);
+    context.insert("tag", &None::<String>);
+
+    render(&templates, "blog/index.html.tera", &context)
+}
+
+pub async fn blog_tag(
+    Path(tag): Path<String>,
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+) -> Result<Html<String>, StatusCode> {
+    let tag = slugify(&tag);
+    let posts: Vec<_> = repo
+        .get_published_posts()
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to load posts: {}", err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_iter()
+        .filter(|post| post.tags.iter().any(|t| slugify(t) == tag))
+        .collect();
+
+    if posts.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut context = Context::new();
+    context.insert("cv_data", cv_data.as_ref());
+    context.insert("assets", asset_paths.as_ref());
+    context.insert("posts", &posts);
+    context.insert("tag", &Some(tag));
+
+    render(&templates, "blog/index.html.tera", &context)
+}
+
+pub async fn blog_post(
+    Path(slug): Path<String>,
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(markdown): Extension<Arc<MarkdownRenderer>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+    Extension(cards): Extension<Arc<SocialCards>>,
+) -> Result<Response, StatusCode> {
+    let now = Utc::now();
+    let post = match repo.get_post_by_slug(&slug).await.map_err(|err| {
+        tracing::error!("Failed to load post {}: {}", slug, err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })? {
+        Some(post) => post,
+        None => {
+            // Renamed posts answer their old slugs with a permanent redirect
+            let renamed = repo
+                .get_post_by_previous_slug(&slug)
+                .await
+                .map_err(|err| {
+                    tracing::error!("Slug history lookup failed for {}: {}", slug, err);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?
+                .filter(|post| post.is_live(now))
+                .ok_or(StatusCode::NOT_FOUND)?;
+            return Ok(moved_permanently(&format!("/blog/{}", renamed.slug)));
+        }
+    };
+
+    if !post.is_live(now) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let rendered = markdown.render(&post.content);
+
+    let mut context = Context::new();
+    context.insert("cv_data", cv_data.as_ref());
+    context.insert("assets", asset_paths.as_ref());
+    context.insert("post", &post);
+    context.insert("toc", &rendered.toc);
+    context.insert("reading_time", &rendered.reading_time_minutes);
+    context.insert("social", &cards.for_post(&post, &cv_data));
+
+    render(&templates, "blog/post.html.tera", &context).map(IntoResponse::into_response)
+}
+
+/// Render a draft or scheduled post through a signed preview link
+pub async fn blog_preview(
+    Path(token): Path<String>,
+    Extension(signer): Extension<Arc<PreviewSigner>>,
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(markdown): Extension<Arc<MarkdownRenderer>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let post_id = signer.verify(&token, Utc::now()).map_err(|err| {
+        tracing::debug!("Rejected preview token: {}", err);
+        StatusCode::NOT_FOUND
+    })?;
+
+    let post = repo
+        .get_post_by_id(post_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to load post {}: {}", post_id, err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let rendered = markdown.render(&post.content);
+
+    let mut context = Context::new();
+    context.insert("cv_data", cv_data.as_ref());
+    context.insert("assets", asset_paths.as_ref());
+    context.insert("post", &post);
+    context.insert("toc", &rendered.toc);
+    context.insert("reading_time", &rendered.reading_time_minutes);
+    context.insert("preview", &true);
+
+    let html = render(&templates, "blog/post.html.tera", &context)?;
+    Ok((
+        [
+            (
+                header::HeaderName::from_static("x-robots-tag"),
+                "noindex, nofollow",
+            ),
+            (header::CACHE_CONTROL, "private, no-store"),
+        ],
+        html,
+    ))
+}
+
+/// 301 to the canonical location of a renamed page
+pub(crate) fn moved_permanently(location: &str) -> Response {
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, location.to_string())],
+    )
+        .into_response()
+}
+
+fn render(templates: &Tera, name: &str, context: &Context) -> Result<Html<String>, StatusCode> {
+    templates.render(name, context).map(Html).map_err(|err| {
+        tracing::error!("Template rendering error: {}", err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::models::CreateBlogPost;
+    use crate::services::create_template_engine;
+
+    #[tokio::test]
+    async fn test_tag_links_lead_to_tag_pages() {
+        let markdown = Arc::new(MarkdownRenderer::new());
+        let cv_data = Arc::new(CVData::default());
+        let templates =
+            create_template_engine(&AppConfig::from_env(), markdown, cv_data.clone()).unwrap();
+        let repo = Arc::new(InMemoryBlogRepository::new());
+        repo.create_post(CreateBlogPost {
+            title: "Systems".to_string(),
+            content: "Body".to_string(),
+            excerpt: None,
+            published: true,
+            publish_at: None,
+            // Tera's builtin slugify transliterates this to "cafe-tips"
+            tags: vec!["Café Tips".to_string()],
+            author: None,
+            change_summary: None,
+        })
+        .await
+        .unwrap();
+        let asset_paths = Arc::new(AssetPaths::default());
+
+        let Html(index) = blog_index(
+            Extension(templates.clone()),
+            Extension(repo.clone()),
+            Extension(cv_data.clone()),
+            Extension(asset_paths.clone()),
+        )
+        .await
+        .unwrap();
+        let link = index
+            .split("href=\"/blog/tag/")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap();
+        assert_eq!(link, slugify("Café Tips"));
+
+        let tag_page = blog_tag(
+            Path(link.to_string()),
+            Extension(templates),
+            Extension(repo),
+            Extension(cv_data),
+            Extension(asset_paths),
+        )
+        .await;
+        assert!(tag_page.is_ok());
+    }
+}
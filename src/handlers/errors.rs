@@ -0,0 +1,163 @@
+use crate::models::CVData;
+use crate::services::AssetPaths;
+use axum::{
+    extract::{Extension, Request},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use std::sync::Arc;
+use tera::{Context, Tera};
+
+/// Router fallback: the styled 404 page, or a bare 404 for clients that
+/// don't accept HTML
+pub async fn not_found(
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+    headers: HeaderMap,
+) -> Response {
+    if !accepts_html(&headers) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    error_page(&templates, &cv_data, &asset_paths, StatusCode::NOT_FOUND)
+}
+
+/// Middleware giving browsers the 404 or 500 page where a handler answered
+/// with a bare status code. Responses with a body of their own, and requests
+/// that don't accept HTML (API clients, scripts, images), are left alone.
+pub async fn error_pages(
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let accepts_html = accepts_html(request.headers());
+    let response = next.run(request).await;
+
+    let status = response.status();
+    let bare = !response.headers().contains_key(header::CONTENT_TYPE);
+    if accepts_html
+        && bare
+        && matches!(
+            status,
+            StatusCode::NOT_FOUND | StatusCode::INTERNAL_SERVER_ERROR
+        )
+    {
+        return error_page(&templates, &cv_data, &asset_paths, status);
+    }
+    response
+}
+
+fn accepts_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// `{status}.html.tera` rendered with `status`; the bare status if that fails
+fn error_page(
+    templates: &Tera,
+    cv_data: &CVData,
+    asset_paths: &AssetPaths,
+    status: StatusCode,
+) -> Response {
+    let mut context = Context::new();
+    context.insert("cv_data", cv_data);
+    context.insert("assets", asset_paths);
+    context.insert("status", &status.as_u16());
+    let digits: Vec<String> = status.as_str().chars().map(String::from).collect();
+    context.insert("status_digits", &digits);
+
+    match templates.render(&format!("{}.html.tera", status.as_u16()), &context) {
+        Ok(html) => (status, Html(html)).into_response(),
+        Err(err) => {
+            tracing::error!("Template rendering error: {}", err);
+            status.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::services::{create_template_engine, MarkdownRenderer};
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let cv_data = Arc::new(CVData::default());
+        let templates = create_template_engine(
+            &AppConfig::from_env(),
+            Arc::new(MarkdownRenderer::new()),
+            cv_data.clone(),
+        )
+        .unwrap();
+
+        Router::new()
+            .route("/boom", get(|| async { StatusCode::INTERNAL_SERVER_ERROR }))
+            .route(
+                "/api/missing",
+                get(|| async { (StatusCode::NOT_FOUND, "No such post") }),
+            )
+            .fallback(not_found)
+            .layer(axum::middleware::from_fn(error_pages))
+            .layer(Extension(templates))
+            .layer(Extension(cv_data))
+            .layer(Extension(Arc::new(AssetPaths::default())))
+    }
+
+    async fn get_with_accept(uri: &str, accept: &str) -> (StatusCode, Option<String>, String) {
+        let request = Request::builder()
+            .uri(uri)
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_fallback_renders_404_page_for_browsers() {
+        let (status, content_type, body) = get_with_accept("/nowhere", "text/html,*/*;q=0.8").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type.as_deref(), Some("text/html; charset=utf-8"));
+        assert!(body.contains("<title>404 - Page Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_fallback_is_bare_for_other_clients() {
+        let (status, content_type, body) = get_with_accept("/nowhere", "application/json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type, None);
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bare_handler_error_gets_500_page() {
+        let (status, content_type, body) = get_with_accept("/boom", "text/html").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(content_type.as_deref(), Some("text/html; charset=utf-8"));
+        assert!(body.contains("<title>500 - Server Error"));
+
+        // Errors that carry their own body are left alone
+        let (status, _, body) = get_with_accept("/api/missing", "text/html").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, "No such post");
+    }
+}
@@ -0,0 +1,227 @@
+use crate::config::AppConfig;
+use crate::models::CVData;
+use crate::services::{BlogRepository, Feed, InMemoryBlogRepository, MarkdownRenderer};
+use crate::utils::{format_http_date, parse_http_date, slugify};
+use axum::{
+    extract::{Extension, Path},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+#[derive(Clone, Copy)]
+enum FeedFormat {
+    Rss,
+    Atom,
+    Json,
+}
+
+impl FeedFormat {
+    fn from_file_name(name: &str) -> Option<Self> {
+        match name {
+            "feed.xml" => Some(FeedFormat::Rss),
+            "atom.xml" => Some(FeedFormat::Atom),
+            "feed.json" => Some(FeedFormat::Json),
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+            FeedFormat::Json => "application/feed+json; charset=utf-8",
+        }
+    }
+
+    fn render(self, feed: &Feed) -> String {
+        match self {
+            FeedFormat::Rss => feed.to_rss(),
+            FeedFormat::Atom => feed.to_atom(),
+            FeedFormat::Json => feed.to_json_feed().to_string(),
+        }
+    }
+}
+
+/// Site and per-tag feeds in every format; the format comes from the
+/// requested file name and the tag from the `/blog/tag/:tag/...` routes
+pub async fn feed(
+    uri: Uri,
+    tag: Option<Path<String>>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(markdown): Extension<Arc<MarkdownRenderer>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+    request_headers: HeaderMap,
+) -> Response {
+    let Some(format) = uri
+        .path()
+        .rsplit('/')
+        .next()
+        .and_then(FeedFormat::from_file_name)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let posts = match repo.get_published_posts().await {
+        Ok(posts) => posts,
+        Err(err) => {
+            tracing::error!("Failed to load posts for feed: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let tag = tag.map(|Path(tag)| slugify(&tag));
+    let feed = Feed::from_posts(
+        &posts,
+        tag.as_deref(),
+        &cv_data.personal_info.name,
+        &config.site_url,
+        &markdown,
+    );
+
+    // Unknown tags have no feed
+    if tag.is_some() && feed.entries.is_empty() {
+        return (StatusCode::NOT_FOUND, "Feed not found").into_response();
+    }
+
+    let body = format.render(&feed);
+    let etag = format!("\"{:x}\"", md5::compute(body.as_bytes()));
+    let last_modified = feed.updated();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, etag.parse().unwrap());
+    headers.insert(
+        header::LAST_MODIFIED,
+        format_http_date(last_modified).parse().unwrap(),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        "public, max-age=900".parse().unwrap(),
+    );
+
+    // Conditional GET: If-None-Match takes precedence over If-Modified-Since
+    let not_modified = match request_headers.get(header::IF_NONE_MATCH) {
+        Some(value) => value
+            .to_str()
+            .map(|value| {
+                value
+                    .split(',')
+                    .any(|tag| tag.trim() == etag || tag.trim() == "*")
+            })
+            .unwrap_or(false),
+        None => request_headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_http_date)
+            .map(|since| last_modified.timestamp() <= since.timestamp())
+            .unwrap_or(false),
+    };
+
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    headers.insert(header::CONTENT_TYPE, format.content_type().parse().unwrap());
+    (headers, body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateBlogPost;
+
+    async fn repo() -> Arc<InMemoryBlogRepository> {
+        let repo = Arc::new(InMemoryBlogRepository::new());
+        repo.create_post(CreateBlogPost {
+            title: "Ownership".to_string(),
+            content: "Borrowing rules".to_string(),
+            excerpt: None,
+            published: true,
+            publish_at: None,
+            tags: vec!["Rust".to_string()],
+            author: None,
+            change_summary: None,
+        })
+        .await
+        .unwrap();
+        repo
+    }
+
+    async fn get(
+        repo: &Arc<InMemoryBlogRepository>,
+        path: &str,
+        tag: Option<&str>,
+        headers: HeaderMap,
+    ) -> Response {
+        feed(
+            path.parse().unwrap(),
+            tag.map(|tag| Path(tag.to_string())),
+            Extension(repo.clone()),
+            Extension(Arc::new(MarkdownRenderer::new())),
+            Extension(Arc::new(CVData::default())),
+            Extension(Arc::new(AppConfig::from_env())),
+            headers,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_format_follows_file_name() {
+        let repo = repo().await;
+        for (path, content_type) in [
+            ("/feed.xml", "application/rss+xml; charset=utf-8"),
+            ("/atom.xml", "application/atom+xml; charset=utf-8"),
+            ("/feed.json", "application/feed+json; charset=utf-8"),
+        ] {
+            let response = get(&repo, path, None, HeaderMap::new()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], content_type);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tag_feeds() {
+        let repo = repo().await;
+        let response = get(
+            &repo,
+            "/blog/tag/Rust/atom.xml",
+            Some("Rust"),
+            HeaderMap::new(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get(&repo, "/blog/tag/go/atom.xml", Some("go"), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_requests() {
+        let repo = repo().await;
+        let response = get(&repo, "/feed.xml", None, HeaderMap::new()).await;
+        let etag = response.headers()[header::ETAG].clone();
+        let last_modified = response.headers()[header::LAST_MODIFIED].clone();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag.clone());
+        let response = get(&repo, "/feed.xml", None, headers).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MODIFIED_SINCE, last_modified);
+        let response = get(&repo, "/feed.xml", None, headers).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // A stale ETag wins over a matching date
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, "\"stale\"".parse().unwrap());
+        headers.insert(
+            header::IF_MODIFIED_SINCE,
+            "Sun, 01 Jan 2090 00:00:00 GMT".parse().unwrap(),
+        );
+        let response = get(&repo, "/feed.xml", None, headers).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
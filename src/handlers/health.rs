@@ -1,6 +1,14 @@
-use axum::{http::StatusCode, response::Json};
+use crate::services::manifest_check::ManifestCheck;
+use crate::services::{PageCache, StaticCache};
+use axum::{
+    extract::Extension,
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
+};
 use chrono::Utc;
 use serde_json::json;
+use std::fmt::Write;
+use std::sync::Arc;
 
 pub async fn health_check() -> Result<Json<serde_json::Value>, StatusCode> {
     Ok(Json(json!({
@@ -11,14 +19,154 @@ pub async fn health_check() -> Result<Json<serde_json::Value>, StatusCode> {
     })))
 }
 
-pub async fn readiness_check() -> Result<Json<serde_json::Value>, StatusCode> {
+/// Ready once the assets referenced by the Vite manifest were all found at
+/// startup; a broken deploy answers 503
+pub async fn readiness_check(
+    Extension(manifest_check): Extension<Arc<ManifestCheck>>,
+) -> impl IntoResponse {
     // Future: Add database connectivity checks here
-    Ok(Json(json!({
-        "status": "ready",
+    let ready = manifest_check.is_ready();
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = Json(json!({
+        "status": if ready { "ready" } else { "not_ready" },
         "timestamp": Utc::now(),
         "checks": {
             "database": "not_configured",
-            "filesystem": "ok"
+            "filesystem": if ready { "ok" } else { "failed" },
+            "assets": {
+                "status": manifest_check.status,
+                "files": manifest_check.verified.len(),
+                "bytes": manifest_check.total_bytes(),
+                "missing": manifest_check.missing,
+            }
         }
-    })))
+    }));
+    (status, body)
+}
+
+/// Prometheus text exposition of runtime counters
+pub async fn metrics(
+    Extension(static_cache): Extension<Arc<StaticCache>>,
+    Extension(page_cache): Extension<Arc<PageCache>>,
+) -> impl IntoResponse {
+    let stats = static_cache.stats();
+    let pages = page_cache.stats();
+    let mut body = String::new();
+
+    let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+        let _ = writeln!(body, "# HELP {} {}", name, help);
+        let _ = writeln!(body, "# TYPE {} {}", name, kind);
+        let _ = writeln!(body, "{} {}", name, value);
+    };
+    metric(
+        "static_cache_hits_total",
+        "counter",
+        "Static file requests served from memory",
+        stats.hits.to_string(),
+    );
+    metric(
+        "static_cache_misses_total",
+        "counter",
+        "Static file requests that loaded from disk",
+        stats.misses.to_string(),
+    );
+    metric(
+        "static_cache_invalidations_total",
+        "counter",
+        "Cached files dropped after changing on disk",
+        stats.invalidations.to_string(),
+    );
+    metric(
+        "static_cache_evictions_total",
+        "counter",
+        "Cached files evicted to stay within budget",
+        stats.evictions.to_string(),
+    );
+    metric(
+        "static_cache_hit_ratio",
+        "gauge",
+        "Share of cacheable lookups served from memory",
+        format!("{:.4}", stats.hit_rate()),
+    );
+    metric(
+        "static_cache_entries",
+        "gauge",
+        "Files held in the static cache",
+        stats.entries.to_string(),
+    );
+    metric(
+        "static_cache_bytes",
+        "gauge",
+        "Bytes held in the static cache, variants included",
+        stats.bytes.to_string(),
+    );
+    metric(
+        "static_cache_max_bytes",
+        "gauge",
+        "Configured static cache budget",
+        stats.max_bytes.to_string(),
+    );
+    metric(
+        "page_cache_hits_total",
+        "counter",
+        "Page requests served from a cached render",
+        pages.hits.to_string(),
+    );
+    metric(
+        "page_cache_misses_total",
+        "counter",
+        "Page requests that rendered a template",
+        pages.misses.to_string(),
+    );
+    metric(
+        "page_cache_invalidations_total",
+        "counter",
+        "Cached pages dropped after templates or content changed",
+        pages.invalidations.to_string(),
+    );
+    metric(
+        "page_cache_evictions_total",
+        "counter",
+        "Cached pages evicted to stay within budget",
+        pages.evictions.to_string(),
+    );
+    metric(
+        "page_cache_hit_ratio",
+        "gauge",
+        "Share of page requests served from a cached render",
+        format!("{:.4}", pages.hit_rate()),
+    );
+    metric(
+        "page_cache_render_seconds_saved_total",
+        "counter",
+        "Template render time avoided by cache hits",
+        format!("{:.6}", pages.render_seconds_saved),
+    );
+    metric(
+        "page_cache_entries",
+        "gauge",
+        "Pages held in the page cache",
+        pages.entries.to_string(),
+    );
+    metric(
+        "page_cache_bytes",
+        "gauge",
+        "Bytes held in the page cache, variants included",
+        pages.bytes.to_string(),
+    );
+
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/plain; version=0.0.4; charset=utf-8",
+            ),
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        body,
+    )
 }
@@ -1,10 +1,25 @@
+pub mod admin;
 pub mod api;
+pub mod blog;
+pub mod errors;
+pub mod feeds;
 pub mod health;
+pub mod og;
 pub mod portfolio;
+pub mod preload;
+pub mod search;
 pub mod static_files;
+pub mod templates;
 
 // Re-export handlers
 pub use api::*;
+pub use blog::*;
+pub use errors::*;
+pub use feeds::*;
 pub use health::*;
+pub use og::*;
 pub use portfolio::*;
+pub use preload::*;
+pub use search::*;
 pub use static_files::*;
+pub use templates::*;
@@ -0,0 +1,59 @@
+use crate::models::CVData;
+use crate::services::{
+    BlogRepository, InMemoryBlogRepository, OgCard, OgImageRenderer, SocialCards,
+};
+use axum::{
+    extract::{Extension, Path},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use std::sync::Arc;
+
+/// Open Graph card for the home page
+pub async fn og_home_image(
+    Extension(cards): Extension<Arc<SocialCards>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    card_response(&cards, OgCard::for_home(&cv_data)).await
+}
+
+/// Open Graph card for a published post, `/og/blog/{slug}.png`
+pub async fn og_post_image(
+    Path(file): Path<String>,
+    Extension(cards): Extension<Arc<SocialCards>>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let slug = file.strip_suffix(".png").ok_or(StatusCode::NOT_FOUND)?;
+    let post = repo
+        .get_post_by_slug(slug)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to load post {}: {}", slug, err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .filter(|post| post.is_live(Utc::now()))
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    card_response(&cards, OgCard::for_post(&post, &cv_data.personal_info.name)).await
+}
+
+async fn card_response(cards: &SocialCards, card: OgCard) -> Result<impl IntoResponse, StatusCode> {
+    // Pages don't link to cards that can't be rendered
+    let renderer: &Arc<OgImageRenderer> = cards.renderer().ok_or(StatusCode::NOT_FOUND)?;
+    let png = renderer.png(card).await.map_err(|err| {
+        tracing::error!("Open Graph card rendering failed: {}", err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // URLs in the meta tags carry the card hash, so a day is safe even
+    // for unversioned requests
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        png,
+    ))
+}
@@ -1,66 +1,211 @@
+use crate::config::AppConfig;
+use crate::handlers::blog::moved_permanently;
 use crate::models::CVData;
-use crate::services::AssetPaths;
+use crate::services::page_cache::PageKey;
+use crate::services::precompress;
+use crate::services::redirects::{RedirectScope, RedirectTable};
+use crate::services::sitemap;
+use crate::services::template::SharedTemplates;
+use crate::services::{
+    AssetPaths, BlogRepository, InMemoryBlogRepository, MarkdownRenderer, PageCache, SitemapPages,
+    SocialCards,
+};
+use crate::utils::{negotiate_locale, slugify};
 use axum::{
-    extract::Extension,
-    http::StatusCode,
-    response::{Html, Json, Response},
+    extract::{Extension, Path},
+    http::{header, HeaderMap, StatusCode},
+    response::{Json, Response},
 };
 use serde_json::json;
 use std::sync::Arc;
-use tera::{Context, Tera};
+use tera::Context;
 
 pub async fn index(
-    Extension(templates): Extension<Arc<Tera>>,
+    Extension(templates): Extension<Arc<SharedTemplates>>,
+    Extension(page_cache): Extension<Arc<PageCache>>,
     Extension(cv_data): Extension<Arc<CVData>>,
     Extension(asset_paths): Extension<Arc<AssetPaths>>,
-) -> Result<Html<String>, StatusCode> {
-    let mut context = Context::new();
-    context.insert("cv_data", cv_data.as_ref());
-    context.insert("assets", asset_paths.as_ref());
+    Extension(cards): Extension<Arc<SocialCards>>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    cached_page(
+        &templates,
+        &page_cache,
+        &headers,
+        "/",
+        "index.html.tera",
+        |context| {
+            context.insert("cv_data", cv_data.as_ref());
+            context.insert("assets", asset_paths.as_ref());
+            context.insert("social", &cards.for_home(&cv_data));
+        },
+    )
+}
+
+pub async fn project_page(
+    Path(slug): Path<String>,
+    Extension(templates): Extension<Arc<SharedTemplates>>,
+    Extension(page_cache): Extension<Arc<PageCache>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+    Extension(redirects): Extension<Arc<RedirectTable>>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let Some(project) = cv_data
+        .projects
+        .iter()
+        .find(|project| slugify(&project.name) == slug)
+    else {
+        // Renamed projects keep answering at their old slugs
+        return redirects
+            .lookup(RedirectScope::Project, &slug)
+            .map(|current| moved_permanently(&format!("/projects/{}", current)))
+            .ok_or(StatusCode::NOT_FOUND);
+    };
+
+    let route = format!("/projects/{}", slug);
+    cached_page(
+        &templates,
+        &page_cache,
+        &headers,
+        &route,
+        "project.html.tera",
+        |context| {
+            context.insert("cv_data", cv_data.as_ref());
+            context.insert("assets", asset_paths.as_ref());
+            context.insert("project", project);
+        },
+    )
+}
 
-    templates
-        .render("index.html.tera", &context)
-        .map(Html)
+/// Render `template` for `route` through the page cache. The context is only
+/// built on a miss; `locale` is added to it.
+fn cached_page(
+    templates: &SharedTemplates,
+    page_cache: &PageCache,
+    headers: &HeaderMap,
+    route: &str,
+    template: &str,
+    fill_context: impl FnOnce(&mut Context),
+) -> Result<Response, StatusCode> {
+    let (tera, template_version) = templates.snapshot();
+    let accept_language = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok());
+    let locale = negotiate_locale(accept_language, page_cache.locales());
+    let key = PageKey {
+        route: route.to_string(),
+        template_version,
+        data_version: page_cache.data_version(),
+        locale: locale.to_string(),
+    };
+
+    let page = page_cache
+        .get_or_render(key, || {
+            let mut context = Context::new();
+            fill_context(&mut context);
+            context.insert("locale", locale);
+            tera.render(template, &context)
+        })
         .map_err(|err| {
             tracing::error!("Template rendering error: {}", err);
             StatusCode::INTERNAL_SERVER_ERROR
-        })
-}
+        })?;
 
-pub async fn robots_txt() -> Response<String> {
-    let content = r#"User-agent: *
-Allow: /
+    // The template error overlay is injected into the plain HTML
+    let accepted = match templates.last_error() {
+        Some(_) => Vec::new(),
+        None => headers
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .map(precompress::negotiate)
+            .unwrap_or_default(),
+    };
+    let mut response = page.response(&accepted);
+    if page_cache.locales().len() > 1 {
+        response.headers_mut().append(
+            header::VARY,
+            header::HeaderValue::from_static("accept-language"),
+        );
+    }
+    Ok(response)
+}
 
-Sitemap: https://dav88.dev/sitemap.xml"#;
+pub async fn robots_txt(Extension(config): Extension<Arc<AppConfig>>) -> Response<String> {
+    let content = format!(
+        "User-agent: *\nAllow: /\nDisallow: /admin\n\nSitemap: {}/sitemap.xml\n",
+        config.site_url
+    );
 
     Response::builder()
         .status(StatusCode::OK)
         .header("content-type", "text/plain")
-        .body(content.to_string())
+        .body(content)
         .unwrap()
 }
 
-pub async fn sitemap_xml() -> Response<String> {
-    let content = r#"<?xml version="1.0" encoding="UTF-8"?>
-<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
-    <url>
-        <loc>https://dav88.dev/</loc>
-        <lastmod>2025-01-01</lastmod>
-        <changefreq>monthly</changefreq>
-        <priority>1.0</priority>
-    </url>
-    <url>
-        <loc>https://dav88.dev/api/cv</loc>
-        <lastmod>2025-01-01</lastmod>
-        <changefreq>monthly</changefreq>
-        <priority>0.8</priority>
-    </url>
-</urlset>"#;
+/// Sitemap of every public page, or a sitemap index once it needs splitting
+pub async fn sitemap_xml(
+    Extension(pages): Extension<Arc<SitemapPages>>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Response<String>, StatusCode> {
+    let urls = sitemap_urls(&pages, &repo, &cv_data).await?;
+    let body = if sitemap::page_count(urls.len()) > 1 {
+        sitemap::render_index(&config.site_url, &urls)
+    } else {
+        sitemap::render_urlset(&config.site_url, &urls)
+    };
+    Ok(xml_response(body))
+}
+
+/// One page of a split sitemap, `/sitemaps/{n}.xml`
+pub async fn sitemap_page(
+    Path(file): Path<String>,
+    Extension(pages): Extension<Arc<SitemapPages>>,
+    Extension(repo): Extension<Arc<InMemoryBlogRepository>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(config): Extension<Arc<AppConfig>>,
+) -> Result<Response<String>, StatusCode> {
+    let number: usize = file
+        .strip_suffix(".xml")
+        .and_then(|number| number.parse().ok())
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let urls = sitemap_urls(&pages, &repo, &cv_data).await?;
+    let urls = sitemap::page(&urls, number).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(xml_response(sitemap::render_urlset(&config.site_url, urls)))
+}
+
+async fn sitemap_urls(
+    pages: &SitemapPages,
+    repo: &InMemoryBlogRepository,
+    cv_data: &CVData,
+) -> Result<Vec<sitemap::SitemapUrl>, StatusCode> {
+    let posts = repo.get_published_posts().await.map_err(|err| {
+        tracing::error!("Failed to load posts for sitemap: {}", err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(sitemap::collect_urls(pages, &posts, cv_data))
+}
+
+fn xml_response(body: String) -> Response<String> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/xml; charset=utf-8")
+        .header("cache-control", "public, max-age=3600")
+        .body(body)
+        .unwrap()
+}
 
+/// Stylesheet for syntax-highlighted code blocks in rendered Markdown
+pub async fn syntax_css(Extension(markdown): Extension<Arc<MarkdownRenderer>>) -> Response<String> {
     Response::builder()
         .status(StatusCode::OK)
-        .header("content-type", "application/xml")
-        .body(content.to_string())
+        .header("content-type", "text/css; charset=utf-8")
+        .header("cache-control", "public, max-age=86400")
+        .body(markdown.highlight_css().to_string())
         .unwrap()
 }
 
@@ -0,0 +1,30 @@
+use crate::services::preload::PreloadLinks;
+use axum::{
+    extract::{Extension, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Middleware adding the route's `Link` preload header to HTML pages
+pub async fn preload_links(
+    Extension(links): Extension<Arc<PreloadLinks>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let mut response = next.run(request).await;
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    if response.status() == StatusCode::OK && is_html {
+        if let Some(value) = links.for_path(&path) {
+            response.headers_mut().append(header::LINK, value.clone());
+        }
+    }
+    response
+}
@@ -0,0 +1,78 @@
+use crate::models::{ApiResponse, CVData};
+use crate::services::{AssetPaths, SearchIndex, SearchResult};
+use axum::{
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::{Html, Json},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tera::{Context, Tera};
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 50;
+const MAX_QUERY_LENGTH: usize = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    pub query: String,
+    pub total: usize,
+    pub results: Vec<SearchResult>,
+}
+
+impl SearchQuery {
+    fn run(&self, index: &SearchIndex) -> SearchResponse {
+        let query: String = self
+            .q
+            .as_deref()
+            .unwrap_or("")
+            .trim()
+            .chars()
+            .take(MAX_QUERY_LENGTH)
+            .collect();
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+        let results = index.search(&query, limit);
+
+        SearchResponse {
+            total: results.len(),
+            query,
+            results,
+        }
+    }
+}
+
+pub async fn search_api(
+    Query(params): Query<SearchQuery>,
+    Extension(index): Extension<Arc<SearchIndex>>,
+) -> Result<Json<ApiResponse<SearchResponse>>, StatusCode> {
+    Ok(Json(ApiResponse::success(params.run(&index))))
+}
+
+pub async fn search_page(
+    Query(params): Query<SearchQuery>,
+    Extension(index): Extension<Arc<SearchIndex>>,
+    Extension(templates): Extension<Arc<Tera>>,
+    Extension(cv_data): Extension<Arc<CVData>>,
+    Extension(asset_paths): Extension<Arc<AssetPaths>>,
+) -> Result<Html<String>, StatusCode> {
+    let response = params.run(&index);
+
+    let mut context = Context::new();
+    context.insert("cv_data", cv_data.as_ref());
+    context.insert("assets", asset_paths.as_ref());
+    context.insert("search", &response);
+
+    templates
+        .render("search.html.tera", &context)
+        .map(Html)
+        .map_err(|err| {
+            tracing::error!("Template rendering error: {}", err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
@@ -1,80 +1,387 @@
+use crate::services::precompress::{self, Encoding};
+use crate::services::ranges::{self, ByteRange, RangeRequest};
+use crate::services::safe_path::PathError;
+use crate::services::vite::ViteDevServer;
+use crate::services::{AssetSource, CachePolicy, StaticCache};
 use axum::{
-    extract::Path,
-    http::{header, HeaderMap, StatusCode},
+    body::{Body, Bytes},
+    extract::{Extension, Path},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
     response::{IntoResponse, Response},
 };
+use futures::stream::{self, StreamExt};
+use std::io::SeekFrom;
 use std::path::PathBuf;
-use tokio::fs;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
 
-/// Custom static file handler with optimized cache headers
-pub async fn serve_static_file(Path(path): Path<String>) -> Response {
-    let static_dir = std::env::var("STATIC_DIR").unwrap_or_else(|_| "static".to_string());
-    let file_path = PathBuf::from(&static_dir).join(&path);
+/// Boundary between parts of a `multipart/byteranges` response
+const BYTERANGES_BOUNDARY: &str = "static-byteranges-7d1f0c3a";
 
-    // Security check - prevent directory traversal
-    if !file_path.starts_with(&static_dir) {
-        return (StatusCode::FORBIDDEN, "Access denied").into_response();
-    }
+/// Custom static file handler with optimized cache headers. Small files are
+/// served from the in-memory cache, larger ones streamed from disk; `Range`
+/// requests get 206 and matching validators get 304. Files missing on disk
+/// (or all files, in an `embed-assets` production build) come from the binary.
+pub async fn serve_static_file(
+    Path(path): Path<String>,
+    Extension(cache): Extension<Arc<StaticCache>>,
+    Extension(source): Extension<Arc<AssetSource>>,
+    Extension(policy): Extension<Arc<CachePolicy>>,
+    Extension(vite): Extension<Option<Arc<ViteDevServer>>>,
+    uri: Uri,
+    request_headers: HeaderMap,
+) -> Response {
+    // Security check - traversal, escaping symlinks and hidden files
+    let path = match source.static_paths().sanitize(&path) {
+        Ok(path) => path,
+        Err(PathError::Hidden) => return (StatusCode::NOT_FOUND, "File not found").into_response(),
+        Err(_) => return (StatusCode::FORBIDDEN, "Access denied").into_response(),
+    };
 
-    // Check if file exists
-    let file_content = match fs::read(&file_path).await {
-        Ok(content) => content,
-        Err(_) => return (StatusCode::NOT_FOUND, "File not found").into_response(),
+    // Check if file exists on disk, when disk files are served at all
+    let disk_file = if source.reads_disk() {
+        match source.static_paths().resolve(&path).await {
+            Ok(file_path) => match fs::metadata(&file_path).await {
+                Ok(metadata) if metadata.is_file() => Some((file_path, metadata)),
+                _ => None,
+            },
+            Err(PathError::OutsideRoot) => {
+                return (StatusCode::FORBIDDEN, "Access denied").into_response()
+            }
+            Err(_) => None,
+        }
+    } else {
+        None
     };
 
-    // Determine content type and cache policy based on file extension
-    let (content_type, cache_control) = get_content_type_and_cache(&path);
+    // Prefer a precompressed variant the client accepts
+    let accepted = request_headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(precompress::negotiate)
+        .unwrap_or_default();
+
+    let cached_file = disk_file
+        .as_ref()
+        .filter(|(_, metadata)| cache.admits(metadata.len()));
+    let (representation, encoding, etag, modified) =
+        if let Some((file_path, metadata)) = cached_file {
+            let cached = match cache.get(file_path, metadata).await {
+                Ok(cached) => cached,
+                Err(_) => return (StatusCode::NOT_FOUND, "File not found").into_response(),
+            };
+            let (bytes, encoding) = cached.variant(&accepted);
+            let etag = cached.etag(encoding);
+            (
+                Representation::Memory(bytes),
+                encoding,
+                etag,
+                cached.modified,
+            )
+        } else if let Some((file_path, metadata)) = &disk_file {
+            let (served_path, encoding) = preferred_file(file_path, &accepted).await;
+            let served = match fs::metadata(&served_path).await {
+                Ok(served) => served,
+                Err(_) => return (StatusCode::NOT_FOUND, "File not found").into_response(),
+            };
+            // ETags come from file metadata, so streaming never means hashing
+            let etag = metadata_etag(served.len(), served.modified().ok(), encoding);
+            let representation = Representation::File {
+                path: served_path,
+                len: served.len(),
+            };
+            (representation, encoding, etag, metadata.modified().ok())
+        } else if let Some(identity) = source.embedded_static(&path) {
+            embedded_variant(&source, &path, identity, &accepted)
+        } else if let Some(vite) = vite {
+            // Frontend sources only exist on the Vite dev server
+            return match vite.proxy(&uri, &request_headers).await {
+                Ok(response) => response,
+                Err(e) => {
+                    tracing::warn!("Vite dev server at {} unreachable: {}", vite.url(), e);
+                    (StatusCode::BAD_GATEWAY, "Vite dev server unreachable").into_response()
+                }
+            };
+        } else {
+            return (StatusCode::NOT_FOUND, "File not found").into_response();
+        };
+    let len = representation.len();
+
+    // Content type by extension; only fingerprinted files are immutable
+    let content_type = get_content_type(&path);
+    let cache_control = policy.cache_control(&path);
 
     // Build response headers
     let mut headers = HeaderMap::new();
-    headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
     headers.insert(header::CACHE_CONTROL, cache_control.parse().unwrap());
-    
+    headers.insert(header::ETAG, etag.parse().unwrap());
+    let last_modified = modified.map(httpdate::fmt_http_date);
+    if let Some(last_modified) = &last_modified {
+        headers.insert(header::LAST_MODIFIED, last_modified.parse().unwrap());
+    }
+    if encoding.is_some() {
+        headers.insert(header::VARY, "accept-encoding".parse().unwrap());
+    }
+
+    if is_not_modified(&request_headers, &etag, modified) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
     // Add security headers
     headers.insert("X-Content-Type-Options", "nosniff".parse().unwrap());
-    
-    // Add ETags for better caching
-    let etag = format!("\"{:x}\"", md5::compute(&file_content));
-    headers.insert(header::ETAG, etag.parse().unwrap());
 
-    (headers, file_content).into_response()
+    // A Content-Encoding header also makes CompressionLayer pass the body through
+    if let Some(encoding) = encoding {
+        headers.insert(header::CONTENT_ENCODING, encoding.token().parse().unwrap());
+    }
+
+    let range = request_headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| if_range_matches(&request_headers, &etag, last_modified.as_deref()))
+        .map_or(RangeRequest::Ignore, |value| ranges::parse(value, len));
+
+    match range {
+        RangeRequest::Ignore => {
+            headers.insert(header::CONTENT_LENGTH, len.into());
+            match representation.body(None).await {
+                Ok(body) => (headers, body).into_response(),
+                Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
+        }
+        RangeRequest::Unsatisfiable => {
+            headers.insert(
+                header::CONTENT_RANGE,
+                format!("bytes */{}", len).parse().unwrap(),
+            );
+            (StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response()
+        }
+        RangeRequest::Satisfiable(ranges) => match ranges.as_slice() {
+            [range] => {
+                headers.insert(
+                    header::CONTENT_RANGE,
+                    range.content_range(len).parse().unwrap(),
+                );
+                headers.insert(header::CONTENT_LENGTH, range.length().into());
+                match representation.body(Some(*range)).await {
+                    Ok(body) => (StatusCode::PARTIAL_CONTENT, headers, body).into_response(),
+                    Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                }
+            }
+            _ => multipart_ranges(&representation, content_type, &ranges, headers).await,
+        },
+    }
+}
+
+/// Embedded file at `path`, or its embedded `.br`/`.gz` sidecar when accepted
+fn embedded_variant(
+    source: &AssetSource,
+    path: &str,
+    identity: &'static [u8],
+    accepted: &[Encoding],
+) -> (Representation, Option<Encoding>, String, Option<SystemTime>) {
+    let modified = Some(source.embedded_modified());
+    let (bytes, encoding) = accepted
+        .iter()
+        .find_map(|&encoding| {
+            source
+                .embedded_static(&format!("{}.{}", path, encoding.extension()))
+                .map(|bytes| (bytes, Some(encoding)))
+        })
+        .unwrap_or((identity, None));
+    let etag = metadata_etag(bytes.len() as u64, modified, encoding);
+    (
+        Representation::Memory(Bytes::from_static(bytes)),
+        encoding,
+        etag,
+        modified,
+    )
+}
+
+/// The bytes a response is served from
+enum Representation {
+    Memory(Bytes),
+    File { path: PathBuf, len: u64 },
+}
+
+impl Representation {
+    fn len(&self) -> u64 {
+        match self {
+            Representation::Memory(bytes) => bytes.len() as u64,
+            Representation::File { len, .. } => *len,
+        }
+    }
+
+    /// The whole representation, or just `range` of it
+    async fn body(&self, range: Option<ByteRange>) -> std::io::Result<Body> {
+        match (self, range) {
+            (Representation::Memory(bytes), None) => Ok(Body::from(bytes.clone())),
+            (Representation::Memory(bytes), Some(range)) => Ok(Body::from(
+                bytes.slice(range.start as usize..=range.end as usize),
+            )),
+            (Representation::File { path, .. }, None) => Ok(Body::from_stream(ReaderStream::new(
+                File::open(path).await?,
+            ))),
+            (Representation::File { path, .. }, Some(range)) => {
+                let mut file = File::open(path).await?;
+                file.seek(SeekFrom::Start(range.start)).await?;
+                Ok(Body::from_stream(ReaderStream::new(
+                    file.take(range.length()),
+                )))
+            }
+        }
+    }
 }
 
-fn get_content_type_and_cache(path: &str) -> (&'static str, &'static str) {
+/// The best sidecar allowed by `Accept-Encoding`, else the file itself
+async fn preferred_file(
+    file_path: &std::path::Path,
+    accepted: &[Encoding],
+) -> (PathBuf, Option<Encoding>) {
+    for &encoding in accepted {
+        let sidecar = precompress::sidecar_path(file_path, encoding);
+        if fs::try_exists(&sidecar).await.unwrap_or(false) {
+            return (sidecar, Some(encoding));
+        }
+    }
+    (file_path.to_path_buf(), None)
+}
+
+fn metadata_etag(len: u64, modified: Option<SystemTime>, encoding: Option<Encoding>) -> String {
+    let modified = modified
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos());
+    match encoding {
+        Some(encoding) => format!("\"{:x}-{:x}-{}\"", modified, len, encoding.extension()),
+        None => format!("\"{:x}-{:x}\"", modified, len),
+    }
+}
+
+/// `If-None-Match` (weak comparison) takes precedence; `If-Modified-Since`
+/// is only consulted without it
+fn is_not_modified(request_headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = request_headers.get(header::IF_NONE_MATCH) {
+        let Ok(if_none_match) = if_none_match.to_str() else {
+            return false;
+        };
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
+    }
+
+    let since = request_headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+    match (since, modified) {
+        // HTTP dates have one-second resolution
+        (Some(since), Some(modified)) => {
+            modified.duration_since(UNIX_EPOCH).is_ok_and(|modified| {
+                since
+                    .duration_since(UNIX_EPOCH)
+                    .is_ok_and(|since| modified.as_secs() <= since.as_secs())
+            })
+        }
+        _ => false,
+    }
+}
+
+/// Whether a `Range` may be honoured: no `If-Range`, or one naming the
+/// current representation (strong ETag or exact `Last-Modified` date)
+fn if_range_matches(request_headers: &HeaderMap, etag: &str, last_modified: Option<&str>) -> bool {
+    let Some(if_range) = request_headers
+        .get(header::IF_RANGE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return true;
+    };
+    if if_range.starts_with('"') {
+        return if_range == etag;
+    }
+    last_modified.is_some_and(|last_modified| if_range == last_modified)
+}
+
+/// 206 with a `multipart/byteranges` body
+async fn multipart_ranges(
+    representation: &Representation,
+    content_type: &str,
+    ranges: &[ByteRange],
+    mut headers: HeaderMap,
+) -> Response {
+    let len = representation.len();
+    let mut parts = Vec::with_capacity(ranges.len() * 2 + 1);
+    let mut content_length = 0u64;
+    for range in ranges {
+        let part_header = format!(
+            "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            BYTERANGES_BOUNDARY,
+            content_type,
+            range.content_range(len)
+        );
+        let Ok(body) = representation.body(Some(*range)).await else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+
+        content_length += part_header.len() as u64 + range.length();
+        parts.push(Body::from(part_header).into_data_stream());
+        parts.push(body.into_data_stream());
+    }
+    let closing = format!("\r\n--{}--\r\n", BYTERANGES_BOUNDARY);
+    content_length += closing.len() as u64;
+    parts.push(Body::from(closing).into_data_stream());
+
+    headers.insert(
+        header::CONTENT_TYPE,
+        format!("multipart/byteranges; boundary={}", BYTERANGES_BOUNDARY)
+            .parse()
+            .unwrap(),
+    );
+    headers.insert(header::CONTENT_LENGTH, content_length.into());
+    let body = Body::from_stream(stream::iter(parts).flatten());
+    (StatusCode::PARTIAL_CONTENT, headers, body).into_response()
+}
+
+fn get_content_type(path: &str) -> &'static str {
     let extension = std::path::Path::new(path)
         .extension()
         .and_then(|ext| ext.to_str())
         .unwrap_or("");
 
     match extension {
-        // Images - long cache (1 year) with immutable
-        "webp" => ("image/webp", "public, max-age=31536000, immutable"),
-        "png" => ("image/png", "public, max-age=31536000, immutable"),
-        "jpg" | "jpeg" => ("image/jpeg", "public, max-age=31536000, immutable"),
-        "svg" => ("image/svg+xml", "public, max-age=31536000, immutable"),
-        "ico" => ("image/x-icon", "public, max-age=31536000, immutable"),
-        
-        // CSS/JS - long cache with versioning
-        "css" => ("text/css; charset=utf-8", "public, max-age=31536000, immutable"),
-        "js" => ("application/javascript; charset=utf-8", "public, max-age=31536000, immutable"),
-        "mjs" => ("application/javascript; charset=utf-8", "public, max-age=31536000, immutable"),
-        
-        // Fonts - very long cache
-        "woff2" => ("font/woff2", "public, max-age=31536000, immutable"),
-        "woff" => ("font/woff", "public, max-age=31536000, immutable"),
-        "ttf" => ("font/ttf", "public, max-age=31536000, immutable"),
-        "otf" => ("font/otf", "public, max-age=31536000, immutable"),
-        
+        // Images
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+
+        // CSS/JS
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "application/javascript; charset=utf-8",
+
+        // Fonts
+        "woff2" => "font/woff2",
+        "woff" => "font/woff",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+
         // WASM files
-        "wasm" => ("application/wasm", "public, max-age=31536000, immutable"),
-        
-        // Manifests and other config files - short cache
-        "json" => ("application/json; charset=utf-8", "public, max-age=3600"),
-        "xml" => ("application/xml; charset=utf-8", "public, max-age=3600"),
-        "txt" => ("text/plain; charset=utf-8", "public, max-age=3600"),
-        "webmanifest" => ("application/manifest+json", "public, max-age=3600"),
-        
-        // Default - no cache for security
-        _ => ("application/octet-stream", "public, max-age=3600"),
+        "wasm" => "application/wasm",
+
+        // Manifests and other config files
+        "json" => "application/json; charset=utf-8",
+        "xml" => "application/xml; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "webmanifest" => "application/manifest+json",
+
+        _ => "application/octet-stream",
     }
-}
\ No newline at end of file
+}
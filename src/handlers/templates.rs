@@ -0,0 +1,61 @@
+use crate::services::template::SharedTemplates;
+use axum::{
+    body::{self, Body},
+    extract::{Extension, Request},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Middleware handing each request the current template engine, so handlers
+/// keep extracting `Extension<Arc<Tera>>` while reloads swap it underneath.
+/// After a failed reload, HTML pages get an overlay with the error.
+/// Compressed bodies, such as pages served from the page cache, pass through
+/// untouched since the overlay can only be spliced into plain HTML.
+pub async fn current_templates(
+    Extension(templates): Extension<Arc<SharedTemplates>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    request.extensions_mut().insert(templates.current());
+    let response = next.run(request).await;
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    let is_encoded = response.headers().contains_key(header::CONTENT_ENCODING);
+    match templates.last_error() {
+        Some(error) if is_html && !is_encoded => with_error_overlay(response, &error).await,
+        _ => response,
+    }
+}
+
+async fn with_error_overlay(response: Response, error: &str) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = body::to_bytes(body, usize::MAX).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+
+    let overlay = format!(
+        r#"<div id="template-error-overlay" style="position:fixed;inset:0;z-index:2147483647;background:rgba(17,24,39,.92);color:#fecaca;font:14px/1.5 ui-monospace,monospace;padding:2rem;overflow:auto"><h2 style="color:#f87171;font-size:1.25rem;margin-bottom:1rem">Template reload failed</h2><p style="color:#e5e7eb;margin-bottom:1rem">Still serving the last working templates. Fix the error and save to reload.</p><pre style="white-space:pre-wrap">{}</pre></div>"#,
+        escape_html(error)
+    );
+    let mut html = String::from_utf8_lossy(&bytes).into_owned();
+    match html.rfind("</body>") {
+        Some(index) => html.insert_str(index, &overlay),
+        None => html.push_str(&overlay),
+    }
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(html))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,12 +1,17 @@
 use axum::{
-    extract::Extension,
-    routing::get,
+    extract::{DefaultBodyLimit, Extension},
+    routing::{get, post, put, MethodRouter},
     Router,
 };
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tower::ServiceBuilder;
 use tower_http::{
-    compression::CompressionLayer,
+    compression::{
+        predicate::{NotForContentType, Predicate},
+        CompressionLayer, DefaultPredicate,
+    },
     cors::CorsLayer,
     trace::TraceLayer,
 };
@@ -21,25 +26,46 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 use personal_website::{
     config::AppConfig,
     handlers::{
-        get_cv_data, get_cv_json, health_check, index, manifest_json, readiness_check, robots_txt,
-        sitemap_xml, serve_static_file,
+        admin, blog_index, blog_post, blog_preview, blog_tag, create_blog_post,
+        create_preview_link, current_templates, delete_blog_post, diff_revisions, error_pages,
+        feed, get_blog_posts, get_cv_data, get_cv_json, get_revision, health_check, index,
+        list_revisions, manifest_json, metrics, not_found, og_home_image, og_post_image,
+        preload_links, project_page, readiness_check, robots_txt, rollback_revision, search_api,
+        search_page, serve_static_file, sitemap_page, sitemap_xml, syntax_css, update_blog_post,
+        upload_image,
     },
     models::CVData,
-    services::{load_asset_paths, template::create_template_engine},
+    services::{
+        asset_gc::{self, GcOptions},
+        database::spawn_publish_scheduler,
+        images::MAX_UPLOAD_BYTES,
+        load_asset_paths,
+        manifest_check::ManifestCheck,
+        page_cache::spawn_page_cache_invalidator,
+        precompress,
+        preview::PreviewSigner,
+        redirects::{RedirectScope, RedirectTable},
+        search::spawn_index_updater,
+        template::{create_template_engine, spawn_template_reloader, SharedTemplates},
+        vite::ViteDevServer,
+        AssetPaths, AssetSource, BlogRepository, CachePolicy, ImageStore, InMemoryBlogRepository,
+        MarkdownRenderer, PageCache, PreloadLinks, RevisionRetention, SearchIndex, SitemapPages,
+        SocialCards, StaticCache,
+    },
 };
 
 #[tokio::main(flavor = "multi_thread")] // Uses num_cpus::get() by default
 async fn main() -> anyhow::Result<()> {
     // Load configuration first
     let config = AppConfig::from_env();
-    
+
     // Initialize logging with production-ready defaults
     let log_level = if config.is_development() {
         "personal_website=debug,tower_http=debug"
     } else {
         "personal_website=info,tower_http=warn"
     };
-    
+
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
@@ -47,22 +73,141 @@ async fn main() -> anyhow::Result<()> {
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
+
+    // One-off maintenance commands, e.g. `personal_website precompress`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some((command, options)) = args.split_first() {
+        return run_command(command, options, &config);
+    }
+
     tracing::info!("Starting server with config: {:?}", config);
 
-    // Initialize template engine
-    let templates = create_template_engine(&config)?;
-    tracing::info!("Template engine initialized");
+    // Static files and templates: on disk, or embedded with `embed-assets`
+    let asset_source = Arc::new(AssetSource::new(&config));
+
+    // Uploaded images, rendered as responsive <picture> elements in posts
+    let images = Arc::new(ImageStore::open(&config.static_dir)?);
+    tracing::info!("Image store opened with {} uploads", images.len());
+
+    // Open Graph cards, rendered on demand and cached on disk
+    let social_cards = Arc::new(SocialCards::new(
+        &asset_source,
+        &config.og_cache_dir,
+        &config.site_url,
+    ));
+
+    // Initialize Markdown renderer (shared by templates and handlers)
+    let markdown = Arc::new(MarkdownRenderer::new().with_images(images.clone()));
 
     // Initialize application data
     let cv_data = Arc::new(CVData::default());
     tracing::info!("CV data loaded");
 
+    // Initialize template engine
+    let templates = Arc::new(SharedTemplates::new(create_template_engine(
+        &config,
+        markdown.clone(),
+        cv_data.clone(),
+    )?));
+    if config.is_development() {
+        spawn_template_reloader(templates.clone(), &config);
+    }
+    tracing::info!("Template engine initialized");
+
+    // Slug history shared by blog posts and project pages
+    let redirects = Arc::new(RedirectTable::new());
+    for (old_slug, new_slug) in &config.project_redirects {
+        redirects.record_rename(RedirectScope::Project, new_slug, old_slug, new_slug);
+    }
+
+    // Initialize blog storage (in-memory until a database is configured)
+    let blog_repo = Arc::new(
+        InMemoryBlogRepository::with_redirects(redirects.clone()).with_revision_retention(
+            RevisionRetention {
+                max_revisions: config.revision_limit,
+                max_age: config.revision_max_age_days.map(chrono::Duration::days),
+            },
+        ),
+    );
+    spawn_publish_scheduler(blog_repo.clone());
+    let preview_signer = Arc::new(PreviewSigner::new(config.preview_secret.expose()));
+
+    // Build the search index and keep it updated as posts change
+    let search_index = Arc::new(SearchIndex::build(
+        &cv_data,
+        &blog_repo.get_published_posts().await?,
+    ));
+    spawn_index_updater(search_index.clone(), blog_repo.clone());
+    tracing::info!("Search index built with {} documents", search_index.len());
+
+    // Rendered pages, dropped whenever the blog changes
+    let page_cache = Arc::new(PageCache::new(
+        config.page_cache_max_bytes,
+        config.site_locales.clone(),
+    ));
+    spawn_page_cache_invalidator(page_cache.clone(), blog_repo.clone());
+
+    // Every file the manifest references must have been deployed
+    let manifest_check = Arc::new(ManifestCheck::run(&asset_source));
+    if manifest_check.is_ready() {
+        tracing::info!(
+            "Vite manifest verified: {} files, {} bytes",
+            manifest_check.verified.len(),
+            manifest_check.total_bytes()
+        );
+    } else {
+        tracing::error!(
+            "Vite manifest check failed ({:?}), missing: {:?}",
+            manifest_check.status,
+            manifest_check.missing
+        );
+    }
+
     // Load asset paths from Vite manifest
-    let asset_paths = Arc::new(load_asset_paths(&config.static_dir)?);
+    let asset_paths = match config.vite_dev_server() {
+        Some(url) => Arc::new(AssetPaths::for_dev_server(url)),
+        None => Arc::new(load_asset_paths(&asset_source)?),
+    };
     tracing::info!("Asset paths loaded");
 
+    // Frontend modules come from Vite with hot reload in development
+    let vite_dev_server = config.vite_dev_server().map(|url| {
+        tracing::info!(
+            "⚡ Proxying missing static files to the Vite dev server at {}",
+            url
+        );
+        Arc::new(ViteDevServer::new(url))
+    });
+
+    // In-memory cache for small static files
+    let cache_policy = Arc::new(CachePolicy::new(&config, &asset_source));
+    let preload_links = Arc::new(PreloadLinks::new(&config, &asset_source));
+    let static_cache = Arc::new(StaticCache::new(
+        config.static_cache_max_bytes,
+        config.static_cache_max_file_bytes,
+    ));
+
     // Build the application with middleware
-    let app = create_app(templates, cv_data, asset_paths, &config).await?;
+    let services = AppServices {
+        templates,
+        cv_data,
+        asset_paths,
+        markdown,
+        blog_repo,
+        search_index,
+        preview_signer,
+        redirects,
+        images,
+        social_cards,
+        static_cache,
+        page_cache,
+        cache_policy,
+        manifest_check,
+        preload_links,
+        asset_source,
+        vite_dev_server,
+    };
+    let app = create_app(services, &config).await?;
 
     // Create TCP listener
     let listener = tokio::net::TcpListener::bind(&config.bind_address()).await?;
@@ -76,11 +221,11 @@ async fn main() -> anyhow::Result<()> {
     use hyper_util::server::conn::auto::Builder;
     use hyper_util::service::TowerToHyperService;
     use tower::Service;
-    
+
     let make_service = app.into_make_service();
     let executor = TokioExecutor::new();
     let builder = Builder::new(executor); // Pure HTTP/2 builder for maximum performance
-    
+
     loop {
         tokio::select! {
             conn_result = listener.accept() => {
@@ -91,7 +236,7 @@ async fn main() -> anyhow::Result<()> {
                         tokio::spawn(async move {
                             let peer_addr = stream.peer_addr().unwrap();
                             let io = TokioIo::new(stream);
-                            
+
                             // Create a service for this connection
                             let service = match make_service_clone.call(peer_addr).await {
                                 Ok(service) => service,
@@ -100,12 +245,12 @@ async fn main() -> anyhow::Result<()> {
                                     return;
                                 }
                             };
-                            
+
                             let hyper_service = TowerToHyperService::new(service);
-                            
+
                             if let Err(err) = builder_clone
                                 .serve_connection(io, hyper_service)
-                                .await 
+                                .await
                             {
                                 tracing::error!("Connection error: {}", err);
                             }
@@ -126,50 +271,223 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn create_app(
-    templates: Arc<tera::Tera>,
+fn run_command(command: &str, options: &[String], config: &AppConfig) -> anyhow::Result<()> {
+    match command {
+        "precompress" => {
+            let report = precompress::generate_sidecars(&config.static_dir)?;
+            tracing::info!(
+                "Wrote {} sidecars ({} already current), saving {} bytes",
+                report.written,
+                report.skipped,
+                report.bytes_saved
+            );
+            Ok(())
+        }
+        "gc-assets" => {
+            let mut gc = GcOptions {
+                dry_run: false,
+                grace: Duration::from_secs(7 * 24 * 3600),
+            };
+            let mut options = options.iter();
+            while let Some(option) = options.next() {
+                match option.as_str() {
+                    "--dry-run" => gc.dry_run = true,
+                    "--grace-days" => {
+                        let days: u64 = options
+                            .next()
+                            .and_then(|days| days.parse().ok())
+                            .ok_or_else(|| {
+                                anyhow::anyhow!("--grace-days needs a number of days")
+                            })?;
+                        gc.grace = Duration::from_secs(days * 24 * 3600);
+                    }
+                    other => anyhow::bail!(
+                        "unknown gc-assets option `{}` (--dry-run, --grace-days N)",
+                        other
+                    ),
+                }
+            }
+
+            let report = asset_gc::collect_static_dir(Path::new(&config.static_dir), &gc)?;
+            let verb = if gc.dry_run {
+                "Would remove"
+            } else {
+                "Removed"
+            };
+            for path in &report.removed {
+                tracing::info!("{} {}", verb, path.display());
+            }
+            for path in &report.in_grace {
+                tracing::info!("Keeping {} (within grace period)", path.display());
+            }
+            tracing::info!(
+                "{} {} orphaned files ({} bytes); {} live, {} kept within the grace period",
+                verb,
+                report.removed.len(),
+                report.bytes_freed,
+                report.live,
+                report.in_grace.len()
+            );
+            Ok(())
+        }
+        other => anyhow::bail!(
+            "unknown command `{}` (available: precompress, gc-assets)",
+            other
+        ),
+    }
+}
+
+/// Shared services handed to handlers as request extensions
+struct AppServices {
+    templates: Arc<SharedTemplates>,
     cv_data: Arc<CVData>,
-    asset_paths: Arc<personal_website::services::AssetPaths>,
-    config: &AppConfig,
-) -> anyhow::Result<Router> {
-    // Set static directory for our custom handler
-    std::env::set_var("STATIC_DIR", &config.static_dir);
+    asset_paths: Arc<AssetPaths>,
+    markdown: Arc<MarkdownRenderer>,
+    blog_repo: Arc<InMemoryBlogRepository>,
+    search_index: Arc<SearchIndex>,
+    preview_signer: Arc<PreviewSigner>,
+    redirects: Arc<RedirectTable>,
+    images: Arc<ImageStore>,
+    social_cards: Arc<SocialCards>,
+    static_cache: Arc<StaticCache>,
+    page_cache: Arc<PageCache>,
+    cache_policy: Arc<CachePolicy>,
+    manifest_check: Arc<ManifestCheck>,
+    preload_links: Arc<PreloadLinks>,
+    asset_source: Arc<AssetSource>,
+    vite_dev_server: Option<Arc<ViteDevServer>>,
+}
+
+/// Router builder that records parameterless HTML pages for the sitemap
+#[derive(Default)]
+struct PageRouter {
+    router: Router,
+    paths: Vec<String>,
+}
+
+impl PageRouter {
+    fn page(mut self, path: &str, route: MethodRouter) -> Self {
+        debug_assert!(
+            !path.contains([':', '*']),
+            "sitemap pages take no parameters"
+        );
+        self.paths.push(path.to_string());
+        self.router = self.router.route(path, route);
+        self
+    }
 
+    fn into_parts(self) -> (Router, SitemapPages) {
+        (self.router, SitemapPages::new(self.paths))
+    }
+}
+
+async fn create_app(services: AppServices, config: &AppConfig) -> anyhow::Result<Router> {
     // Middleware stack optimized for multi-threaded performance with Brotli + Gzip
     let compression = CompressionLayer::new()
-        .br(true)  // Enable Brotli compression
+        .br(true) // Enable Brotli compression
         .gzip(true) // Enable Gzip compression
-        .no_deflate(); // Disable deflate to focus on better algorithms
-        
+        .no_deflate() // Disable deflate to focus on better algorithms
+        // Byte-range parts must reach the client exactly as sliced
+        .compress_when(
+            DefaultPredicate::new().and(NotForContentType::const_new("multipart/byteranges")),
+        );
+
+    // HTML pages registered through `page` are also listed in the sitemap
+    let (pages_router, pages) = PageRouter::default()
+        .page("/", get(index))
+        .page("/blog", get(blog_index))
+        .into_parts();
+
     let middleware = ServiceBuilder::new()
         .layer(TraceLayer::new_for_http())
         .layer(compression)
         .layer(CorsLayer::permissive()) // Configure as needed
-        .layer(Extension(templates))
-        .layer(Extension(cv_data))
-        .layer(Extension(asset_paths));
+        .layer(Extension(services.templates))
+        .layer(axum::middleware::from_fn(current_templates))
+        .layer(Extension(services.cv_data))
+        .layer(Extension(services.asset_paths))
+        .layer(Extension(services.markdown))
+        .layer(Extension(services.blog_repo))
+        .layer(Extension(services.search_index))
+        .layer(Extension(services.preview_signer))
+        .layer(Extension(services.redirects))
+        .layer(Extension(services.images))
+        .layer(Extension(services.social_cards))
+        .layer(Extension(services.static_cache))
+        .layer(Extension(services.page_cache))
+        .layer(Extension(services.cache_policy))
+        .layer(Extension(services.manifest_check))
+        .layer(Extension(services.asset_source))
+        .layer(Extension(services.vite_dev_server))
+        .layer(Extension(Arc::new(pages)))
+        .layer(Extension(Arc::new(config.clone())))
+        .layer(Extension(services.preload_links))
+        .layer(axum::middleware::from_fn(preload_links))
+        .layer(axum::middleware::from_fn(error_pages));
 
     // Build routes
-    let app = Router::new()
+    let app = pages_router
         // Portfolio routes
-        .route("/", get(index))
         .route("/robots.txt", get(robots_txt))
         .route("/sitemap.xml", get(sitemap_xml))
+        .route("/sitemaps/:file", get(sitemap_page))
         .route("/manifest.json", get(manifest_json))
+        .route("/syntax.css", get(syntax_css))
+        .route("/projects/:slug", get(project_page))
+        .route("/search", get(search_page))
+        // Open Graph cards
+        .route("/og/home.png", get(og_home_image))
+        .route("/og/blog/:file", get(og_post_image))
+        // Blog routes
+        .route("/blog/tag/:tag", get(blog_tag))
+        .route("/blog/preview/:token", get(blog_preview))
+        .route("/blog/:slug", get(blog_post))
+        // Admin area
+        .route("/admin", get(admin::admin_index))
+        .route("/admin/login", get(admin::login_page).post(admin::login))
+        .route("/admin/logout", post(admin::logout))
+        .route("/admin/posts/:id/revisions", get(admin::post_revisions))
+        .route(
+            "/admin/posts/:id/revisions/:number/rollback",
+            post(admin::rollback_revision),
+        )
+        // Feeds
+        .route("/feed.xml", get(feed))
+        .route("/atom.xml", get(feed))
+        .route("/feed.json", get(feed))
+        .route("/blog/tag/:tag/feed.xml", get(feed))
+        .route("/blog/tag/:tag/atom.xml", get(feed))
+        .route("/blog/tag/:tag/feed.json", get(feed))
         // API routes
         .nest(
             "/api",
             Router::new()
                 .route("/cv", get(get_cv_data))
-                .route("/cv.json", get(get_cv_json)), // Future blog routes
-                                                      // .route("/blog", get(get_blog_posts))
-                                                      // .route("/blog", post(create_blog_post))
+                .route("/cv.json", get(get_cv_json))
+                .route("/v1/search", get(search_api))
+                .route("/blog", get(get_blog_posts).post(create_blog_post))
+                .route("/blog/:id", put(update_blog_post).delete(delete_blog_post))
+                .route("/blog/:id/preview", post(create_preview_link))
+                .route("/blog/:id/revisions", get(list_revisions))
+                .route("/blog/:id/revisions/diff", get(diff_revisions))
+                .route("/blog/:id/revisions/:number", get(get_revision))
+                .route(
+                    "/blog/:id/revisions/:number/rollback",
+                    post(rollback_revision),
+                )
+                .route(
+                    "/uploads/images",
+                    post(upload_image).layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES + 64 * 1024)),
+                ),
         )
         // Health check routes
         .route("/health", get(health_check))
         .route("/ready", get(readiness_check))
+        .route("/metrics", get(metrics))
         // Static files with optimized cache headers
         .route("/static/*path", get(serve_static_file))
+        // Styled 404 for everything else
+        .fallback(not_found)
         // Apply middleware
         .layer(middleware);
 
@@ -63,18 +63,55 @@ pub struct BlogPost {
     pub content: String,
     pub excerpt: Option<String>,
     pub published: bool,
+    /// Scheduled go-live time; a published post stays hidden until then
+    pub publish_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub tags: Vec<String>,
 }
 
+impl BlogPost {
+    /// Whether readers can see the post at `now`
+    pub fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.published && self.publish_at.is_none_or(|at| at <= now)
+    }
+
+    /// Public publication date: the scheduled time if any, else creation time
+    pub fn published_at(&self) -> DateTime<Utc> {
+        self.publish_at.unwrap_or(self.created_at)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CreateBlogPost {
     pub title: String,
     pub content: String,
     pub excerpt: Option<String>,
     pub published: bool,
+    #[serde(default)]
+    pub publish_at: Option<DateTime<Utc>>,
     pub tags: Vec<String>,
+    /// Who made this change, recorded in revision history
+    #[serde(default)]
+    pub author: Option<String>,
+    /// Short description of the change, recorded in revision history
+    #[serde(default)]
+    pub change_summary: Option<String>,
+}
+
+/// Snapshot of a post as it was saved at one point in time
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlogRevision {
+    pub post_id: Uuid,
+    /// Sequential per post, starting at 1
+    pub number: u32,
+    pub title: String,
+    pub content: String,
+    pub excerpt: Option<String>,
+    pub tags: Vec<String>,
+    pub author: String,
+    pub summary: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 // API response wrappers
@@ -0,0 +1,231 @@
+// Garbage collection of stale Vite build output
+//
+// Vite writes content-hashed files next to those of earlier builds, so the
+// static tree accumulates orphans. The live set is everything reachable from
+// the manifest's entries through `imports`, `dynamicImports`, `css` and
+// `assets`; any other hashed file (and its `.br`/`.gz` sidecars) is an
+// orphan. A file becomes orphaned when a newer build replaces the manifest,
+// so orphans are kept until both they and the manifest are older than the
+// grace period: pages cached by browsers or CDNs may still reference them.
+
+use crate::services::assets::{AssetInfo, MANIFEST_PATH};
+use crate::services::cache_policy::has_vite_hash;
+use crate::services::precompress::Encoding;
+use anyhow::{Context as _, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+/// Directories under the static root that never hold build output
+const SKIPPED_DIRS: &[&str] = &["uploads", ".vite"];
+
+#[derive(Debug, Clone)]
+pub struct GcOptions {
+    /// Report orphans without deleting them
+    pub dry_run: bool,
+    /// Orphans modified more recently than this are kept
+    pub grace: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct GcReport {
+    /// Live files according to the manifest
+    pub live: usize,
+    /// Orphans deleted, or that would be deleted in a dry run
+    pub removed: Vec<PathBuf>,
+    /// Orphans kept because they are still within the grace period
+    pub in_grace: Vec<PathBuf>,
+    pub bytes_freed: u64,
+}
+
+/// Files (relative to the static root) reachable from the manifest's entries
+pub fn live_files(manifest: &HashMap<String, AssetInfo>) -> HashSet<String> {
+    let mut live = HashSet::new();
+    let mut visited = HashSet::new();
+    let mut pending: Vec<&str> = manifest
+        .iter()
+        .filter(|(_, asset)| asset.is_entry == Some(true))
+        .map(|(key, _)| key.as_str())
+        .collect();
+
+    while let Some(key) = pending.pop() {
+        if !visited.insert(key) {
+            continue;
+        }
+        let Some(asset) = manifest.get(key) else {
+            continue;
+        };
+        live.insert(asset.file.clone());
+        live.extend(asset.css.iter().flatten().cloned());
+        live.extend(asset.assets.iter().flatten().cloned());
+        pending.extend(
+            asset
+                .imports
+                .iter()
+                .chain(asset.dynamic_imports.iter())
+                .flatten()
+                .map(String::as_str),
+        );
+    }
+    live
+}
+
+/// Collect garbage in `static_dir` against the Vite manifest on disk there
+pub fn collect_static_dir(static_dir: &Path, options: &GcOptions) -> Result<GcReport> {
+    let manifest_path = static_dir.join(MANIFEST_PATH);
+    let content = std::fs::read(&manifest_path).with_context(|| {
+        format!(
+            "reading {}; build the frontend first",
+            manifest_path.display()
+        )
+    })?;
+    let manifest: HashMap<String, AssetInfo> = serde_json::from_slice(&content)?;
+    let manifest_modified = std::fs::metadata(&manifest_path)?.modified()?;
+    collect_garbage(static_dir, &manifest, manifest_modified, options)
+}
+
+/// Find hashed files under `static_dir` outside the live set and delete them
+/// (unless `options.dry_run`) once the grace period has passed.
+/// `manifest_modified` is when the current build was written.
+pub fn collect_garbage(
+    static_dir: &Path,
+    manifest: &HashMap<String, AssetInfo>,
+    manifest_modified: SystemTime,
+    options: &GcOptions,
+) -> Result<GcReport> {
+    let live = live_files(manifest);
+    anyhow::ensure!(
+        !live.is_empty(),
+        "the Vite manifest has no entries; refusing to treat every asset as an orphan"
+    );
+
+    let mut report = GcReport {
+        live: live.len(),
+        ..Default::default()
+    };
+    let cutoff = SystemTime::now()
+        .checked_sub(options.grace)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+
+    let walker = WalkDir::new(static_dir).into_iter().filter_entry(|entry| {
+        entry.depth() != 1 || !SKIPPED_DIRS.iter().any(|dir| entry.file_name() == *dir)
+    });
+    for entry in walker {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(static_dir)?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        // Sidecars live and die with the file they compress
+        let original = Encoding::ALL
+            .iter()
+            .find_map(|encoding| relative.strip_suffix(&format!(".{}", encoding.extension())))
+            .unwrap_or(&relative);
+        if !has_vite_hash(original) || live.contains(original) {
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+        if metadata.modified()?.max(manifest_modified) > cutoff {
+            report.in_grace.push(entry.path().to_path_buf());
+            continue;
+        }
+        if !options.dry_run {
+            std::fs::remove_file(entry.path())?;
+        }
+        report.bytes_freed += metadata.len();
+        report.removed.push(entry.path().to_path_buf());
+    }
+
+    report.removed.sort();
+    report.in_grace.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collects_only_unreachable_hashed_files() {
+        let manifest: HashMap<String, AssetInfo> = serde_json::from_str(
+            r#"{
+                "src/js/main.js": {
+                    "file": "js/main-AbC123xY.js",
+                    "isEntry": true,
+                    "imports": ["_core-Dq3kLm9x.js"],
+                    "dynamicImports": ["src/js/effects.js"],
+                    "css": ["css/style-Zz9Yy8Xx.css"]
+                },
+                "_core-Dq3kLm9x.js": { "file": "js/core-Dq3kLm9x.js" },
+                "src/js/effects.js": { "file": "js/effects-BDNgvF05.js" }
+            }"#,
+        )
+        .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("asset-gc-{}", uuid::Uuid::new_v4()));
+        for file in [
+            "js/main-AbC123xY.js",
+            "js/main-AbC123xY.js.br",
+            "js/core-Dq3kLm9x.js",
+            "js/effects-BDNgvF05.js",
+            "css/style-Zz9Yy8Xx.css",
+            "js/main-OldBld12.js",
+            "js/main-OldBld12.js.gz",
+            "js/clean-skills.js",
+            "uploads/photo-AbC123xY.png",
+        ] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "x").unwrap();
+        }
+
+        let options = GcOptions {
+            dry_run: true,
+            grace: Duration::from_secs(3600),
+        };
+        let built = SystemTime::now();
+        let report = collect_garbage(&dir, &manifest, built, &options).unwrap();
+        assert_eq!(report.live, 4);
+        assert!(report.removed.is_empty());
+        assert_eq!(
+            report.in_grace,
+            vec![
+                dir.join("js/main-OldBld12.js"),
+                dir.join("js/main-OldBld12.js.gz")
+            ]
+        );
+
+        // Orphaned a day ago, past a one hour grace period
+        let options = GcOptions {
+            dry_run: false,
+            grace: Duration::from_secs(3600),
+        };
+        let built = SystemTime::now() - Duration::from_secs(24 * 3600);
+        let files_built = std::fs::FileTimes::new().set_modified(built);
+        for file in ["js/main-OldBld12.js", "js/main-OldBld12.js.gz"] {
+            std::fs::File::options()
+                .write(true)
+                .open(dir.join(file))
+                .unwrap()
+                .set_times(files_built)
+                .unwrap();
+        }
+        let report = collect_garbage(&dir, &manifest, built, &options).unwrap();
+        assert_eq!(report.removed.len(), 2);
+        assert!(!dir.join("js/main-OldBld12.js").exists());
+        assert!(dir.join("js/main-AbC123xY.js.br").exists());
+        assert!(dir.join("js/clean-skills.js").exists());
+        assert!(dir.join("uploads/photo-AbC123xY.png").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,7 +1,7 @@
+use crate::services::embedded::AssetSource;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssetInfo {
@@ -10,7 +10,10 @@ pub struct AssetInfo {
     #[serde(rename = "isEntry")]
     pub is_entry: Option<bool>,
     pub imports: Option<Vec<String>>,
+    #[serde(rename = "dynamicImports")]
+    pub dynamic_imports: Option<Vec<String>>,
     pub css: Option<Vec<String>>,
+    pub assets: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -36,20 +39,41 @@ impl Default for AssetPaths {
     }
 }
 
-pub fn load_asset_paths(static_dir: &str) -> Result<AssetPaths> {
-    let manifest_path = Path::new(static_dir).join(".vite/manifest.json");
+/// Vite manifest, relative to the static root
+pub const MANIFEST_PATH: &str = ".vite/manifest.json";
 
+/// The Vite manifest, if the frontend has been built
+pub fn read_manifest(source: &AssetSource) -> Result<Option<HashMap<String, AssetInfo>>> {
+    match source.read_static(MANIFEST_PATH) {
+        Some(content) => Ok(Some(serde_json::from_slice(&content)?)),
+        None => Ok(None),
+    }
+}
+
+impl AssetPaths {
+    /// Unbundled sources on a Vite dev server; there are no legacy builds
+    pub fn for_dev_server(url: &str) -> Self {
+        let module = |path: &str| format!("{}/static/{}", url, path);
+        Self {
+            css_main: module("css/style.css"),
+            js_main: module("js/main.js"),
+            js_three_scene: module("js/three-scene.js"),
+            js_main_legacy: module("js/main.js"),
+            js_three_scene_legacy: module("js/three-scene.js"),
+            js_polyfills_legacy: String::new(),
+        }
+    }
+}
+
+pub fn load_asset_paths(source: &AssetSource) -> Result<AssetPaths> {
     // If manifest doesn't exist, return default paths for development
-    if !manifest_path.exists() {
+    let Some(manifest) = read_manifest(source)? else {
         tracing::warn!(
             "Vite manifest not found at {:?}, using default asset paths",
-            manifest_path
+            source.static_dir().join(MANIFEST_PATH)
         );
         return Ok(AssetPaths::default());
-    }
-
-    let manifest_content = std::fs::read_to_string(&manifest_path)?;
-    let manifest: HashMap<String, AssetInfo> = serde_json::from_str(&manifest_content)?;
+    };
 
     let mut asset_paths = AssetPaths::default();
 
@@ -0,0 +1,221 @@
+// Cache-Control for static files
+//
+// Only content-hashed files can be cached forever: their name changes with
+// their content. Those are the files listed in the Vite manifest or, when no
+// manifest is deployed, anything named like Vite output (`main-BIC1Mt9F.js`).
+// Everything else, like `js/clean-skills.js` or `images/photo-20240101.jpg`,
+// is cached briefly and then revalidated against its ETag. Per-path overrides
+// from the config win over both.
+
+use crate::config::AppConfig;
+use crate::services::assets::read_manifest;
+use crate::services::embedded::AssetSource;
+use std::collections::HashSet;
+
+/// Content-hashed files never change under the same name
+pub const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// Other files are reused for a few minutes, then revalidated
+pub const REVALIDATE: &str = "public, max-age=300, must-revalidate";
+
+/// Length of the hash Vite appends to file names
+const HASH_LEN: usize = 8;
+
+pub struct CachePolicy {
+    /// Manifest files, relative to the static root; `None` without a
+    /// manifest, when file names are checked for a Vite hash instead
+    fingerprinted: Option<HashSet<String>>,
+    overrides: Vec<(String, String)>,
+}
+
+impl CachePolicy {
+    pub fn new(config: &AppConfig, source: &AssetSource) -> Self {
+        let fingerprinted = match read_manifest(source) {
+            Ok(Some(manifest)) => Some(
+                manifest
+                    .into_values()
+                    .flat_map(|asset| {
+                        std::iter::once(asset.file)
+                            .chain(asset.css.unwrap_or_default())
+                            .chain(asset.assets.unwrap_or_default())
+                    })
+                    .collect(),
+            ),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable Vite manifest for cache policy: {}", e);
+                None
+            }
+        };
+        let overrides = config
+            .static_cache_control
+            .iter()
+            .filter(|(pattern, value)| {
+                let valid = axum::http::HeaderValue::from_str(value).is_ok();
+                if !valid {
+                    tracing::warn!("Ignoring invalid Cache-Control override for {}", pattern);
+                }
+                valid
+            })
+            .cloned()
+            .collect();
+        Self::with_manifest(fingerprinted, overrides)
+    }
+
+    /// Policy from manifest file paths, if a manifest is deployed, and
+    /// `(pattern, Cache-Control)` overrides, as described on
+    /// [`CachePolicy::cache_control`]
+    pub fn with_manifest(
+        fingerprinted: Option<HashSet<String>>,
+        overrides: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            fingerprinted,
+            overrides,
+        }
+    }
+
+    /// `Cache-Control` for a static file path relative to the static root.
+    /// The first matching override wins; a pattern is an exact path, a
+    /// directory ending in `/`, or `*.ext`.
+    pub fn cache_control(&self, path: &str) -> &str {
+        let matching = self.overrides.iter().find(|(pattern, _)| {
+            if let Some(extension) = pattern.strip_prefix("*.") {
+                path.rsplit_once('.')
+                    .is_some_and(|(_, ext)| ext == extension)
+            } else if pattern.ends_with('/') {
+                path.starts_with(pattern.as_str())
+            } else {
+                path == pattern
+            }
+        });
+        if let Some((_, cache_control)) = matching {
+            return cache_control;
+        }
+
+        if self.is_fingerprinted(path) {
+            IMMUTABLE
+        } else {
+            REVALIDATE
+        }
+    }
+
+    /// Manifest files and their source maps, or Vite-style names without a
+    /// manifest
+    pub fn is_fingerprinted(&self, path: &str) -> bool {
+        match &self.fingerprinted {
+            Some(files) => {
+                files.contains(path)
+                    || path
+                        .strip_suffix(".map")
+                        .is_some_and(|file| files.contains(file))
+            }
+            None => has_vite_hash(path),
+        }
+    }
+}
+
+/// Whether the file name looks like Vite output: `{name}-{hash}.{ext}`,
+/// with an 8 character base64url hash (`main-BIC1Mt9F.js`). Hashes of digits
+/// only are taken for dates (`photo-20240101.jpg`).
+pub fn has_vite_hash(path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let Some((stem, _)) = file_name.split_once('.') else {
+        return false;
+    };
+    let cut = stem.len().saturating_sub(HASH_LEN);
+    let (Some(name), Some(hash)) = (stem.get(..cut), stem.get(cut..)) else {
+        return false;
+    };
+    if !name.ends_with('-') || name.len() == 1 {
+        return false;
+    }
+    let alphabet = hash
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    // Tell hashes from words such as `-polyfill` or `-Vertical`
+    let random = hash.chars().enumerate().any(|(index, c)| {
+        c.is_ascii_digit() || c == '_' || c == '-' || (index > 0 && c.is_ascii_uppercase())
+    });
+    let date = hash.chars().all(|c| c.is_ascii_digit());
+    alphabet && random && !date
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_manifest_files_are_immutable_with_a_manifest() {
+        let policy = CachePolicy::with_manifest(
+            Some(HashSet::from([
+                "js/main-BIC1Mt9F.js".to_string(),
+                "assets/photo.png".to_string(),
+            ])),
+            Vec::new(),
+        );
+
+        for hashed in [
+            "js/main-BIC1Mt9F.js",
+            "js/main-BIC1Mt9F.js.map",
+            "assets/photo.png",
+        ] {
+            assert_eq!(policy.cache_control(hashed), IMMUTABLE, "{}", hashed);
+        }
+
+        for plain in [
+            "images/photo-20240101.jpg",
+            "images/photo-2024a101.jpg",
+            "js/animations-cgkLa7_G.js",
+            "js/clean-skills.js",
+        ] {
+            assert_eq!(policy.cache_control(plain), REVALIDATE, "{}", plain);
+        }
+    }
+
+    #[test]
+    fn test_vite_names_are_immutable_without_a_manifest() {
+        let policy = CachePolicy::with_manifest(
+            None,
+            vec![
+                (
+                    "js/vendor/".to_string(),
+                    "public, max-age=86400".to_string(),
+                ),
+                ("*.webmanifest".to_string(), "no-cache".to_string()),
+                ("robots.txt".to_string(), "public, max-age=60".to_string()),
+            ],
+        );
+
+        for hashed in [
+            "js/animations-BIC1Mt9F.js",
+            "js/core-legacy-4icS2-8z.js",
+            "js/animations-cgkLa7_G.js",
+            "css/style-Dq3kLm9x.css",
+            "js/main-BIC1Mt9F.js.map",
+        ] {
+            assert_eq!(policy.cache_control(hashed), IMMUTABLE, "{}", hashed);
+        }
+
+        for plain in [
+            "js/clean-skills.js",
+            "images/logo.png",
+            "images/photo-20240101.jpg",
+            "js/main-legacy.js",
+            "js/polyfills-polyfill.js",
+            "js/-BIC1Mt9F.js",
+            "css/style.css",
+            "fonts/inter-variable.woff2",
+        ] {
+            assert_eq!(policy.cache_control(plain), REVALIDATE, "{}", plain);
+        }
+
+        assert_eq!(
+            policy.cache_control("js/vendor/gsap.min.js"),
+            "public, max-age=86400"
+        );
+        assert_eq!(policy.cache_control("site.webmanifest"), "no-cache");
+        assert_eq!(policy.cache_control("robots.txt"), "public, max-age=60");
+        assert_eq!(policy.cache_control("docs/robots.txt"), REVALIDATE);
+    }
+}
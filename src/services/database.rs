@@ -3,18 +3,67 @@
 // This module will handle database operations when we add SurrealDB later.
 // For now, it contains the structure and interfaces for future implementation.
 
-use crate::models::{BlogPost, CreateBlogPost};
+use crate::models::{BlogPost, BlogRevision, CreateBlogPost};
+use crate::services::redirects::{RedirectScope, RedirectTable};
+use crate::services::revisions::{RevisionRetention, RevisionStore};
+use crate::utils::slugify;
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Change notifications emitted by repositories so derived data (search
+/// index, caches) can update incrementally instead of rebuilding
+#[derive(Debug, Clone)]
+pub enum BlogEvent {
+    Saved(BlogPost),
+    Deleted(Uuid),
+}
 
 #[allow(async_fn_in_trait)]
 pub trait BlogRepository {
     async fn get_all_posts(&self) -> Result<Vec<BlogPost>>;
-    async fn get_post_by_id(&self, id: uuid::Uuid) -> Result<Option<BlogPost>>;
+    async fn get_post_by_id(&self, id: Uuid) -> Result<Option<BlogPost>>;
     async fn get_post_by_slug(&self, slug: &str) -> Result<Option<BlogPost>>;
+    /// Post that previously used `slug` before being renamed
+    async fn get_post_by_previous_slug(&self, slug: &str) -> Result<Option<BlogPost>>;
     async fn create_post(&self, post: CreateBlogPost) -> Result<BlogPost>;
-    async fn update_post(&self, id: uuid::Uuid, post: CreateBlogPost) -> Result<Option<BlogPost>>;
-    async fn delete_post(&self, id: uuid::Uuid) -> Result<bool>;
+    async fn update_post(&self, id: Uuid, post: CreateBlogPost) -> Result<Option<BlogPost>>;
+    async fn delete_post(&self, id: Uuid) -> Result<bool>;
     async fn get_published_posts(&self) -> Result<Vec<BlogPost>>;
+    /// Saved revisions of a post, newest first
+    async fn list_revisions(&self, post_id: Uuid) -> Result<Vec<BlogRevision>>;
+    async fn get_revision(&self, post_id: Uuid, number: u32) -> Result<Option<BlogRevision>>;
+    /// Restore the content of an earlier revision as a new revision
+    async fn rollback_post(
+        &self,
+        post_id: Uuid,
+        number: u32,
+        author: Option<String>,
+    ) -> Result<Option<BlogPost>>;
+}
+
+/// How often scheduled posts are checked for release
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Periodically release scheduled posts whose `publish_at` has passed
+pub fn spawn_publish_scheduler(repo: Arc<InMemoryBlogRepository>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+        let mut last_run = Utc::now();
+
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+            for post in repo.release_scheduled_posts(last_run, now) {
+                tracing::info!("Scheduled post went live: {}", post.slug);
+            }
+            last_run = now;
+        }
+    });
 }
 
 // Future SurrealDB implementation
@@ -34,12 +83,14 @@ pub trait BlogRepository {
 // For remote usage (when scaling later):
 // let db = surrealdb::Surreal::new::<surrealdb::engine::remote::ws::Ws>("127.0.0.1:8000").await?;
 
-#[allow(dead_code)]
+/// In-memory repository used until a database is configured
 pub struct InMemoryBlogRepository {
-    // Placeholder for development/testing
+    posts: RwLock<HashMap<Uuid, BlogPost>>,
+    redirects: Arc<RedirectTable>,
+    revisions: RevisionStore,
+    events: broadcast::Sender<BlogEvent>,
 }
 
-#[allow(dead_code)]
 impl Default for InMemoryBlogRepository {
     fn default() -> Self {
         Self::new()
@@ -48,41 +99,294 @@ impl Default for InMemoryBlogRepository {
 
 impl InMemoryBlogRepository {
     pub fn new() -> Self {
-        Self {}
+        Self::with_redirects(Arc::new(RedirectTable::new()))
+    }
+
+    /// Share a redirect table so renamed posts stay reachable at old slugs
+    pub fn with_redirects(redirects: Arc<RedirectTable>) -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            posts: RwLock::new(HashMap::new()),
+            redirects,
+            revisions: RevisionStore::default(),
+            events,
+        }
+    }
+
+    /// Replace the default revision retention policy
+    pub fn with_revision_retention(mut self, retention: RevisionRetention) -> Self {
+        self.revisions = RevisionStore::new(retention);
+        self
+    }
+
+    /// Slug for `title` that no other post uses now or used historically,
+    /// suffixed `-2`, `-3`, ... on collision
+    fn unique_slug(&self, posts: &HashMap<Uuid, BlogPost>, title: &str, id: Uuid) -> String {
+        let base = match slugify(title) {
+            slug if slug.is_empty() => "post".to_string(),
+            slug => slug,
+        };
+        let owner = id.to_string();
+        let taken = |candidate: &str| {
+            posts
+                .values()
+                .any(|post| post.slug == candidate && post.id != id)
+                || self
+                    .redirects
+                    .lookup(RedirectScope::Blog, candidate)
+                    .is_some_and(|previous_owner| previous_owner != owner)
+        };
+
+        if !taken(&base) {
+            return base;
+        }
+        (2..)
+            .map(|n| format!("{}-{}", base, n))
+            .find(|candidate| !taken(candidate))
+            .expect("an unused slug suffix always exists")
+    }
+
+    /// Subscribe to post changes made through this repository
+    pub fn subscribe(&self) -> broadcast::Receiver<BlogEvent> {
+        self.events.subscribe()
+    }
+
+    /// Announce scheduled posts whose go-live time fell in `(since, now]`
+    /// so search and caches pick them up; returns the released posts
+    pub fn release_scheduled_posts(
+        &self,
+        since: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Vec<BlogPost> {
+        let released: Vec<BlogPost> = self
+            .posts
+            .read()
+            .unwrap()
+            .values()
+            .filter(|post| post.published)
+            .filter(|post| post.publish_at.is_some_and(|at| at > since && at <= now))
+            .cloned()
+            .collect();
+
+        for post in &released {
+            self.notify(BlogEvent::Saved(post.clone()));
+        }
+        released
+    }
+
+    fn notify(&self, event: BlogEvent) {
+        // No subscribers is fine; nothing derived needs updating
+        let _ = self.events.send(event);
     }
 }
 
-// Placeholder implementation for future use
 impl BlogRepository for InMemoryBlogRepository {
     async fn get_all_posts(&self) -> Result<Vec<BlogPost>> {
-        Ok(vec![])
+        let mut posts: Vec<BlogPost> = self.posts.read().unwrap().values().cloned().collect();
+        posts.sort_by_key(|post| std::cmp::Reverse(post.created_at));
+        Ok(posts)
+    }
+
+    async fn get_post_by_id(&self, id: Uuid) -> Result<Option<BlogPost>> {
+        Ok(self.posts.read().unwrap().get(&id).cloned())
     }
 
-    async fn get_post_by_id(&self, _id: uuid::Uuid) -> Result<Option<BlogPost>> {
-        Ok(None)
+    async fn get_post_by_slug(&self, slug: &str) -> Result<Option<BlogPost>> {
+        Ok(self
+            .posts
+            .read()
+            .unwrap()
+            .values()
+            .find(|post| post.slug == slug)
+            .cloned())
     }
 
-    async fn get_post_by_slug(&self, _slug: &str) -> Result<Option<BlogPost>> {
-        Ok(None)
+    async fn get_post_by_previous_slug(&self, slug: &str) -> Result<Option<BlogPost>> {
+        let Some(id) = self.redirects.lookup(RedirectScope::Blog, slug) else {
+            return Ok(None);
+        };
+        let id: Uuid = id.parse()?;
+        Ok(self.posts.read().unwrap().get(&id).cloned())
     }
 
-    async fn create_post(&self, _post: CreateBlogPost) -> Result<BlogPost> {
-        anyhow::bail!("Not implemented - database required")
+    async fn create_post(&self, post: CreateBlogPost) -> Result<BlogPost> {
+        let now = Utc::now();
+        let id = Uuid::new_v4();
+        let (author, summary) = (post.author, post.change_summary);
+        let mut posts = self.posts.write().unwrap();
+        let post = BlogPost {
+            id,
+            slug: self.unique_slug(&posts, &post.title, id),
+            title: post.title,
+            content: post.content,
+            excerpt: post.excerpt,
+            published: post.published,
+            publish_at: post.publish_at,
+            created_at: now,
+            updated_at: now,
+            tags: post.tags,
+        };
+
+        posts.insert(post.id, post.clone());
+        drop(posts);
+
+        self.revisions.record(&post, author, summary);
+        self.notify(BlogEvent::Saved(post.clone()));
+        Ok(post)
+    }
+
+    async fn update_post(&self, id: Uuid, post: CreateBlogPost) -> Result<Option<BlogPost>> {
+        let updated = {
+            let mut posts = self.posts.write().unwrap();
+            let slug = self.unique_slug(&posts, &post.title, id);
+            let Some(existing) = posts.get_mut(&id) else {
+                return Ok(None);
+            };
+
+            if existing.slug != slug {
+                self.redirects.record_rename(
+                    RedirectScope::Blog,
+                    &id.to_string(),
+                    &existing.slug,
+                    &slug,
+                );
+                existing.slug = slug;
+            }
+            existing.title = post.title;
+            existing.content = post.content;
+            existing.excerpt = post.excerpt;
+            existing.published = post.published;
+            existing.publish_at = post.publish_at;
+            existing.tags = post.tags;
+            existing.updated_at = Utc::now();
+            existing.clone()
+        };
+
+        self.revisions
+            .record(&updated, post.author, post.change_summary);
+
+        self.notify(BlogEvent::Saved(updated.clone()));
+        Ok(Some(updated))
+    }
+
+    async fn delete_post(&self, id: Uuid) -> Result<bool> {
+        let removed = self.posts.write().unwrap().remove(&id).is_some();
+        if removed {
+            self.redirects.forget(RedirectScope::Blog, &id.to_string());
+            self.revisions.forget(id);
+            self.notify(BlogEvent::Deleted(id));
+        }
+        Ok(removed)
+    }
+
+    async fn get_published_posts(&self) -> Result<Vec<BlogPost>> {
+        let now = Utc::now();
+        let mut posts: Vec<BlogPost> = self
+            .posts
+            .read()
+            .unwrap()
+            .values()
+            .filter(|post| post.is_live(now))
+            .cloned()
+            .collect();
+        posts.sort_by_key(|post| std::cmp::Reverse(post.published_at()));
+        Ok(posts)
     }
 
-    async fn update_post(
+    async fn list_revisions(&self, post_id: Uuid) -> Result<Vec<BlogRevision>> {
+        Ok(self.revisions.list(post_id))
+    }
+
+    async fn get_revision(&self, post_id: Uuid, number: u32) -> Result<Option<BlogRevision>> {
+        Ok(self.revisions.get(post_id, number))
+    }
+
+    async fn rollback_post(
         &self,
-        _id: uuid::Uuid,
-        _post: CreateBlogPost,
+        post_id: Uuid,
+        number: u32,
+        author: Option<String>,
     ) -> Result<Option<BlogPost>> {
-        Ok(None)
+        let Some(revision) = self.revisions.get(post_id, number) else {
+            return Ok(None);
+        };
+        let Some(current) = self.get_post_by_id(post_id).await? else {
+            return Ok(None);
+        };
+
+        // Content is restored; publication state stays as it is now
+        self.update_post(
+            post_id,
+            CreateBlogPost {
+                title: revision.title,
+                content: revision.content,
+                excerpt: revision.excerpt,
+                published: current.published,
+                publish_at: current.publish_at,
+                tags: revision.tags,
+                author,
+                change_summary: Some(format!("Rolled back to revision {}", number)),
+            },
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduled(title: &str, publish_at: DateTime<Utc>) -> CreateBlogPost {
+        CreateBlogPost {
+            title: title.to_string(),
+            content: "Coming soon".to_string(),
+            excerpt: None,
+            published: true,
+            publish_at: Some(publish_at),
+            tags: Vec::new(),
+            author: None,
+            change_summary: None,
+        }
     }
 
-    async fn delete_post(&self, _id: uuid::Uuid) -> Result<bool> {
-        Ok(false)
+    #[tokio::test]
+    async fn test_scheduled_post_is_hidden_until_due() {
+        let repo = InMemoryBlogRepository::new();
+        let publish_at = Utc::now() + chrono::Duration::milliseconds(200);
+        repo.create_post(scheduled("Launch", publish_at))
+            .await
+            .unwrap();
+        assert!(repo.get_published_posts().await.unwrap().is_empty());
+        assert!(repo.get_post_by_slug("launch").await.unwrap().is_some());
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let published = repo.get_published_posts().await.unwrap();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].published_at(), publish_at);
     }
 
-    async fn get_published_posts(&self) -> Result<Vec<BlogPost>> {
-        Ok(vec![])
+    #[tokio::test]
+    async fn test_release_announces_posts_that_became_due() {
+        let repo = InMemoryBlogRepository::new();
+        let now = Utc::now();
+        let due = repo
+            .create_post(scheduled("Due", now - chrono::Duration::seconds(10)))
+            .await
+            .unwrap();
+        repo.create_post(scheduled("Earlier", now - chrono::Duration::minutes(5)))
+            .await
+            .unwrap();
+        repo.create_post(scheduled("Later", now + chrono::Duration::minutes(5)))
+            .await
+            .unwrap();
+
+        let mut events = repo.subscribe();
+        let released = repo.release_scheduled_posts(now - chrono::Duration::seconds(30), now);
+        assert_eq!(released.len(), 1);
+        match events.try_recv() {
+            Ok(BlogEvent::Saved(post)) => assert_eq!(post.id, due.id),
+            other => panic!("expected a Saved event, got {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
     }
 }
@@ -0,0 +1,99 @@
+// Static files and templates, on disk or compiled into the binary
+//
+// With the `embed-assets` feature the whole `static/` tree (minus uploads) and
+// every template are embedded at build time, so the binary deploys alone. In
+// development, files on disk still take precedence so edits show up without
+// a rebuild.
+
+use crate::config::AppConfig;
+use crate::services::safe_path::StaticPathResolver;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "embed-assets")]
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
+}
+
+#[cfg(not(feature = "embed-assets"))]
+mod generated {
+    pub const BUILT_AT: u64 = 0;
+    pub static STATIC_FILES: &[(&str, &[u8])] = &[];
+    pub static TEMPLATES: &[(&str, &str)] = &[];
+}
+
+/// Where static files and templates are read from
+pub struct AssetSource {
+    static_dir: PathBuf,
+    templates_dir: PathBuf,
+    disk: bool,
+    static_paths: StaticPathResolver,
+}
+
+impl AssetSource {
+    /// Disk only without embedded assets; with them, disk overrides are
+    /// honoured in development only
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            static_dir: PathBuf::from(&config.static_dir),
+            templates_dir: PathBuf::from(&config.templates_dir),
+            disk: !cfg!(feature = "embed-assets") || config.is_development(),
+            static_paths: StaticPathResolver::new(
+                &config.static_dir,
+                config.static_hidden_allowlist.clone(),
+            ),
+        }
+    }
+
+    pub fn static_dir(&self) -> &Path {
+        &self.static_dir
+    }
+
+    pub fn templates_dir(&self) -> &Path {
+        &self.templates_dir
+    }
+
+    /// Resolver for request paths under the static root
+    pub fn static_paths(&self) -> &StaticPathResolver {
+        &self.static_paths
+    }
+
+    /// Whether files on disk are consulted (before embedded ones)
+    pub fn reads_disk(&self) -> bool {
+        self.disk
+    }
+
+    /// Embedded static file at `path`, relative to the static root
+    pub fn embedded_static(&self, path: &str) -> Option<&'static [u8]> {
+        generated::STATIC_FILES
+            .binary_search_by(|(name, _)| (*name).cmp(path))
+            .ok()
+            .map(|index| generated::STATIC_FILES[index].1)
+    }
+
+    /// Static file contents, from disk when allowed, else embedded
+    pub fn read_static(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        if self.disk {
+            if let Ok(contents) = std::fs::read(self.static_dir.join(path)) {
+                return Some(Cow::Owned(contents));
+            }
+        }
+        self.embedded_static(path).map(Cow::Borrowed)
+    }
+
+    /// Whether a static file exists, on disk when allowed or embedded
+    pub fn has_static(&self, path: &str) -> bool {
+        (self.disk && self.static_dir.join(path).is_file()) || self.embedded_static(path).is_some()
+    }
+
+    /// Embedded templates as `(name, source)`
+    pub fn embedded_templates(&self) -> &'static [(&'static str, &'static str)] {
+        generated::TEMPLATES
+    }
+
+    /// Modification time reported for embedded files: the build time
+    pub fn embedded_modified(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(generated::BUILT_AT)
+    }
+}
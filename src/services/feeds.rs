@@ -0,0 +1,433 @@
+// Syndication feeds for published blog posts
+//
+// Builds RSS 2.0, Atom 1.0 and JSON Feed 1.1 documents from the same list of
+// entries so the three formats never drift apart.
+
+use crate::models::BlogPost;
+use crate::services::markdown::MarkdownRenderer;
+use crate::utils::{slugify, xml_escape};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct FeedEntry {
+    pub id: Uuid,
+    pub title: String,
+    pub url: String,
+    pub summary: Option<String>,
+    pub content_html: String,
+    pub published: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub title: String,
+    pub description: String,
+    pub author: String,
+    pub site_url: String,
+    /// HTML page the feed mirrors, e.g. `/blog` or `/blog/tag/rust`
+    pub html_path: String,
+    /// Prefix the feed documents are served under (empty for the site feed)
+    pub feed_prefix: String,
+    pub entries: Vec<FeedEntry>,
+}
+
+impl Feed {
+    /// Build a feed from published posts, optionally restricted to one tag
+    /// (matched by slug so `Rust` and `rust` are the same tag)
+    pub fn from_posts(
+        posts: &[BlogPost],
+        tag: Option<&str>,
+        author: &str,
+        site_url: &str,
+        markdown: &MarkdownRenderer,
+    ) -> Self {
+        let entries = posts
+            .iter()
+            .filter(|post| match tag {
+                Some(tag) => post.tags.iter().any(|t| slugify(t) == tag),
+                None => true,
+            })
+            .map(|post| {
+                let url = format!("{}/blog/{}", site_url, post.slug);
+                FeedEntry {
+                    id: post.id,
+                    title: post.title.clone(),
+                    summary: post.excerpt.clone(),
+                    // Readers show content away from the site, where
+                    // relative links have nothing to resolve against
+                    content_html: absolute_urls(
+                        &markdown.render(&post.content).html,
+                        site_url,
+                        &url,
+                    ),
+                    published: post.published_at(),
+                    updated: post.updated_at,
+                    tags: post.tags.clone(),
+                    url,
+                }
+            })
+            .collect();
+
+        let (title, html_path, feed_prefix) = match tag {
+            Some(tag) => {
+                let path = format!("/blog/tag/{}", tag);
+                (format!("{} - Blog: {}", author, tag), path.clone(), path)
+            }
+            None => (
+                format!("{} - Blog", author),
+                "/blog".to_string(),
+                String::new(),
+            ),
+        };
+
+        Self {
+            title,
+            description: format!("Articles by {}", author),
+            author: author.to_string(),
+            site_url: site_url.to_string(),
+            html_path,
+            feed_prefix,
+            entries,
+        }
+    }
+
+    /// Most recent modification across all entries, used for `updated`,
+    /// `lastBuildDate` and the `Last-Modified` response header
+    pub fn updated(&self) -> DateTime<Utc> {
+        self.entries
+            .iter()
+            .map(|entry| entry.updated)
+            .max()
+            .unwrap_or(DateTime::<Utc>::UNIX_EPOCH)
+    }
+
+    fn html_url(&self) -> String {
+        format!("{}{}", self.site_url, self.html_path)
+    }
+
+    fn feed_url(&self, file: &str) -> String {
+        format!("{}{}/{}", self.site_url, self.feed_prefix, file)
+    }
+
+    pub fn to_rss(&self) -> String {
+        let mut out = String::from(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+<channel>
+"#,
+        );
+        out.push_str(&format!("<title>{}</title>\n", xml_escape(&self.title)));
+        out.push_str(&format!("<link>{}</link>\n", xml_escape(&self.html_url())));
+        out.push_str(&format!(
+            "<description>{}</description>\n",
+            xml_escape(&self.description)
+        ));
+        out.push_str(&format!(
+            "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+            xml_escape(&self.feed_url("feed.xml"))
+        ));
+        out.push_str(&format!(
+            "<lastBuildDate>{}</lastBuildDate>\n",
+            self.updated().to_rfc2822()
+        ));
+
+        for entry in &self.entries {
+            out.push_str("<item>\n");
+            out.push_str(&format!("<title>{}</title>\n", xml_escape(&entry.title)));
+            out.push_str(&format!("<link>{}</link>\n", xml_escape(&entry.url)));
+            out.push_str(&format!(
+                "<guid isPermaLink=\"false\">urn:uuid:{}</guid>\n",
+                entry.id
+            ));
+            out.push_str(&format!(
+                "<pubDate>{}</pubDate>\n",
+                entry.published.to_rfc2822()
+            ));
+            if let Some(summary) = &entry.summary {
+                out.push_str(&format!(
+                    "<description>{}</description>\n",
+                    xml_escape(summary)
+                ));
+            }
+            out.push_str(&format!(
+                "<content:encoded>{}</content:encoded>\n",
+                xml_escape(&entry.content_html)
+            ));
+            for tag in &entry.tags {
+                out.push_str(&format!("<category>{}</category>\n", xml_escape(tag)));
+            }
+            out.push_str("</item>\n");
+        }
+
+        out.push_str("</channel>\n</rss>\n");
+        out
+    }
+
+    pub fn to_atom(&self) -> String {
+        let mut out = String::from(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+"#,
+        );
+        out.push_str(&format!("<id>{}</id>\n", xml_escape(&self.html_url())));
+        out.push_str(&format!("<title>{}</title>\n", xml_escape(&self.title)));
+        out.push_str(&format!(
+            "<subtitle>{}</subtitle>\n",
+            xml_escape(&self.description)
+        ));
+        out.push_str(&format!(
+            "<updated>{}</updated>\n",
+            self.updated().to_rfc3339()
+        ));
+        out.push_str(&format!(
+            "<link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n",
+            xml_escape(&self.html_url())
+        ));
+        out.push_str(&format!(
+            "<link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>\n",
+            xml_escape(&self.feed_url("atom.xml"))
+        ));
+        out.push_str(&format!(
+            "<author><name>{}</name></author>\n",
+            xml_escape(&self.author)
+        ));
+
+        for entry in &self.entries {
+            out.push_str("<entry>\n");
+            out.push_str(&format!("<id>urn:uuid:{}</id>\n", entry.id));
+            out.push_str(&format!("<title>{}</title>\n", xml_escape(&entry.title)));
+            out.push_str(&format!(
+                "<link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n",
+                xml_escape(&entry.url)
+            ));
+            out.push_str(&format!(
+                "<published>{}</published>\n",
+                entry.published.to_rfc3339()
+            ));
+            out.push_str(&format!(
+                "<updated>{}</updated>\n",
+                entry.updated.to_rfc3339()
+            ));
+            if let Some(summary) = &entry.summary {
+                out.push_str(&format!("<summary>{}</summary>\n", xml_escape(summary)));
+            }
+            out.push_str(&format!(
+                "<content type=\"html\">{}</content>\n",
+                xml_escape(&entry.content_html)
+            ));
+            for tag in &entry.tags {
+                out.push_str(&format!("<category term=\"{}\"/>\n", xml_escape(tag)));
+            }
+            out.push_str("</entry>\n");
+        }
+
+        out.push_str("</feed>\n");
+        out
+    }
+
+    pub fn to_json_feed(&self) -> serde_json::Value {
+        let items: Vec<serde_json::Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "id": format!("urn:uuid:{}", entry.id),
+                    "url": entry.url,
+                    "title": entry.title,
+                    "summary": entry.summary,
+                    "content_html": entry.content_html,
+                    "date_published": entry.published.to_rfc3339(),
+                    "date_modified": entry.updated.to_rfc3339(),
+                    "tags": entry.tags,
+                })
+            })
+            .collect();
+
+        json!({
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": self.title,
+            "description": self.description,
+            "home_page_url": self.html_url(),
+            "feed_url": self.feed_url("feed.json"),
+            "language": "en",
+            "authors": [{ "name": self.author, "url": self.site_url }],
+            "items": items,
+        })
+    }
+}
+
+/// `html` with root-relative `href`, `src` and `srcset` URLs made absolute
+/// against `site_url`, and fragment-only links against `page_url`
+fn absolute_urls(html: &str, site_url: &str, page_url: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        out.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        // The tag runs to the first `>` outside a quoted attribute value
+        let mut quoted = false;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                c == '>' && !quoted
+            })
+            .map_or(rest.len(), |(index, _)| index + 1);
+        rewrite_tag_urls(&rest[..end], site_url, page_url, &mut out);
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn rewrite_tag_urls(tag: &str, site_url: &str, page_url: &str, out: &mut String) {
+    let mut rest = tag;
+    while let Some(start) = rest.find("=\"") {
+        let attribute = rest[..start]
+            .rsplit(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or_default();
+        out.push_str(&rest[..start + 2]);
+        rest = &rest[start + 2..];
+        let end = rest.find('"').unwrap_or(rest.len());
+        let value = &rest[..end];
+        match attribute {
+            "href" | "src" => out.push_str(&absolute_url(value, site_url, page_url)),
+            "srcset" => {
+                let candidates: Vec<String> = value
+                    .split(", ")
+                    .map(|candidate| absolute_url(candidate, site_url, page_url))
+                    .collect();
+                out.push_str(&candidates.join(", "));
+            }
+            _ => out.push_str(value),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+}
+
+fn absolute_url(url: &str, site_url: &str, page_url: &str) -> String {
+    if url.starts_with('/') && !url.starts_with("//") {
+        format!("{}{}", site_url, url)
+    } else if url.starts_with('#') {
+        format!("{}{}", page_url, url)
+    } else {
+        url.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(title: &str, content: &str, tags: &[&str]) -> BlogPost {
+        let now = Utc::now();
+        BlogPost {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            slug: slugify(title),
+            content: content.to_string(),
+            excerpt: Some(format!("About {}", title)),
+            published: true,
+            publish_at: None,
+            created_at: now,
+            updated_at: now,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    fn feed(tag: Option<&str>) -> Feed {
+        let posts = [
+            post("Ownership", "See [the guide](/blog/guide#borrowing) and [below](#notes).\n\n## Notes\n\n![Diagram](/uploads/diagram.png)", &["Rust"]),
+            post("Flexbox", "Centering things", &["CSS"]),
+        ];
+        Feed::from_posts(
+            &posts,
+            tag,
+            "Ada",
+            "https://example.com",
+            &MarkdownRenderer::new(),
+        )
+    }
+
+    #[test]
+    fn test_rss() {
+        let rss = feed(None).to_rss();
+        assert!(rss.contains("<title>Ada - Blog</title>"));
+        assert!(rss.contains("<link>https://example.com/blog/ownership</link>"));
+        assert!(rss.contains(
+            "<atom:link href=\"https://example.com/feed.xml\" rel=\"self\" type=\"application/rss+xml\"/>"
+        ));
+        assert_eq!(rss.matches("<item>").count(), 2);
+        assert!(rss.contains("<category>Rust</category>"));
+        assert!(rss.contains("<description>About Ownership</description>"));
+    }
+
+    #[test]
+    fn test_atom() {
+        let atom = feed(None).to_atom();
+        assert!(atom.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">"));
+        assert!(atom.contains("<author><name>Ada</name></author>"));
+        assert!(atom.contains(
+            "<link rel=\"self\" type=\"application/atom+xml\" href=\"https://example.com/atom.xml\"/>"
+        ));
+        assert_eq!(atom.matches("<entry>").count(), 2);
+        assert!(atom.contains("<category term=\"CSS\"/>"));
+    }
+
+    #[test]
+    fn test_json_feed() {
+        let json = feed(None).to_json_feed();
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["home_page_url"], "https://example.com/blog");
+        assert_eq!(json["feed_url"], "https://example.com/feed.json");
+        assert_eq!(json["items"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            json["items"][0]["url"],
+            "https://example.com/blog/ownership"
+        );
+        assert_eq!(json["items"][0]["tags"][0], "Rust");
+    }
+
+    #[test]
+    fn test_tag_feed_only_has_tagged_posts() {
+        let feed = feed(Some("rust"));
+        assert_eq!(feed.entries.len(), 1);
+        assert_eq!(feed.entries[0].title, "Ownership");
+        assert_eq!(feed.title, "Ada - Blog: rust");
+
+        let json = feed.to_json_feed();
+        assert_eq!(json["home_page_url"], "https://example.com/blog/tag/rust");
+        assert_eq!(
+            json["feed_url"],
+            "https://example.com/blog/tag/rust/feed.json"
+        );
+    }
+
+    #[test]
+    fn test_content_urls_are_absolute() {
+        let content = &feed(Some("rust")).entries[0].content_html;
+        assert!(content.contains("href=\"https://example.com/blog/guide#borrowing\""));
+        assert!(content.contains("href=\"https://example.com/blog/ownership#notes\""));
+        assert!(content.contains("src=\"https://example.com/uploads/diagram.png\""));
+        assert!(!content.contains("=\"/"));
+        assert!(!content.contains("=\"#"));
+    }
+
+    #[test]
+    fn test_absolute_urls_leave_text_and_other_urls_alone() {
+        let html = r#"<p>Write <code>href="/x"</code> like <a href="https://other.dev/">this</a> or <a href="//cdn.dev/a">that</a></p><img srcset="/a.webp 1x, /b.webp 2x" alt="a > b">"#;
+        assert_eq!(
+            absolute_urls(html, "https://example.com", "https://example.com/blog/p"),
+            r#"<p>Write <code>href="/x"</code> like <a href="https://other.dev/">this</a> or <a href="//cdn.dev/a">that</a></p><img srcset="https://example.com/a.webp 1x, https://example.com/b.webp 2x" alt="a > b">"#
+        );
+    }
+}
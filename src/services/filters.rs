@@ -0,0 +1,372 @@
+// Site-specific Tera filters and functions
+//
+// Tera's builtins don't match what the rest of the site does: its `slugify`
+// disagrees with the slugs our routes use, and its `date` has no way to format
+// in a reader's time zone. These replace or extend them so templates format
+// with the same logic as the handlers:
+//
+// - `date(format, tz)`: format a timestamp, RFC 3339 string or `YYYY-MM-DD`
+// - `truncate_words(count, end)`: cut text at a word boundary, collapsing
+//   whitespace
+// - `slugify`: the slug used in `/blog/tag/...` and `/projects/...` URLs
+// - `markdown(toc)`: sanitized HTML for Markdown source
+// - `reading_time`: minutes to read Markdown source
+// - `pluralize(singular, plural)`: `1 post`, `3 posts`
+// - `json_ld`: a JSON-LD `<script>` for any value
+// - `asset_url(path)`: the built URL of a static file or Vite entry
+
+use crate::services::assets::AssetInfo;
+use crate::services::markdown::{MarkdownFilter, MarkdownRenderer};
+use crate::services::structured_data::script_tag;
+use crate::services::vite::BASE;
+use crate::utils::slugify;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use tera::{Tera, Value};
+
+/// Format used by `date` without a `format` argument
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Ending appended by `truncate_words` when text is cut
+const DEFAULT_TRUNCATION_END: &str = "…";
+
+/// Register the site's filters, replacing Tera builtins of the same name
+pub fn register_site_filters(tera: &mut Tera, markdown: Arc<MarkdownRenderer>) {
+    tera.register_filter("date", date);
+    tera.register_filter("truncate_words", truncate_words);
+    tera.register_filter("slugify", slugify_filter);
+    tera.register_filter("markdown", MarkdownFilter::new(markdown.clone()));
+    tera.register_filter("reading_time", ReadingTimeFilter { markdown });
+    tera.register_filter("pluralize", pluralize);
+    tera.register_filter("json_ld", JsonLdFilter);
+}
+
+/// Register `asset_url`, resolving paths through `manifest` and `dev_server`
+/// the same way `vite_entry` does
+pub fn register_asset_url(
+    tera: &mut Tera,
+    manifest: Option<HashMap<String, AssetInfo>>,
+    dev_server: Option<String>,
+) {
+    tera.register_function(
+        "asset_url",
+        AssetUrlFunction {
+            manifest,
+            dev_server,
+        },
+    );
+}
+
+/// `date(format="%B %-d, %Y", tz="Europe/London")`. Times are shown in UTC
+/// unless `tz` names an IANA time zone; plain dates are never shifted.
+fn date(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let format = optional_str(args, "format", "date")?.unwrap_or(DEFAULT_DATE_FORMAT);
+    let tz: Tz = match optional_str(args, "tz", "date")? {
+        Some(name) => name.parse().map_err(|_| {
+            tera::Error::msg(format!("Filter `date`: unknown time zone `{}`", name))
+        })?,
+        None => Tz::UTC,
+    };
+
+    let mut formatted = String::new();
+    let written = match parse_date(value)? {
+        ParsedDate::Time(time) => write!(formatted, "{}", time.with_timezone(&tz).format(format)),
+        ParsedDate::Day(day) => write!(formatted, "{}", day.format(format)),
+    };
+    written.map_err(|_| tera::Error::msg(format!("Filter `date`: invalid format `{}`", format)))?;
+    Ok(Value::String(formatted))
+}
+
+enum ParsedDate {
+    Time(DateTime<Utc>),
+    Day(NaiveDate),
+}
+
+fn parse_date(value: &Value) -> tera::Result<ParsedDate> {
+    let parsed = match value {
+        Value::Number(number) => number
+            .as_i64()
+            .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+            .map(ParsedDate::Time),
+        Value::String(text) => DateTime::parse_from_rfc3339(text)
+            .map(|time| ParsedDate::Time(time.with_timezone(&Utc)))
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f")
+                    .map(|time| ParsedDate::Time(time.and_utc()))
+            })
+            .or_else(|_| NaiveDate::parse_from_str(text, "%Y-%m-%d").map(ParsedDate::Day))
+            .ok(),
+        _ => None,
+    };
+    parsed.ok_or_else(|| {
+        tera::Error::msg(format!(
+            "Filter `date` expects a timestamp or date string, got `{}`",
+            value
+        ))
+    })
+}
+
+/// `truncate_words(count=30, end="…")`: the first `count` words, with `end`
+/// appended only when something was cut
+fn truncate_words(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let text = expect_str(value, "truncate_words")?;
+    let count =
+        args.get("count").and_then(Value::as_u64).ok_or_else(|| {
+            tera::Error::msg("Filter `truncate_words` requires a `count` argument")
+        })? as usize;
+    let end = optional_str(args, "end", "truncate_words")?.unwrap_or(DEFAULT_TRUNCATION_END);
+
+    // Whitespace runs collapse to single spaces whether or not text is cut
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.len() <= count {
+        return Ok(Value::String(words.join(" ")));
+    }
+    Ok(Value::String(format!(
+        "{}{}",
+        words[..count].join(" "),
+        end
+    )))
+}
+
+fn slugify_filter(value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    Ok(Value::String(slugify(expect_str(value, "slugify")?)))
+}
+
+/// `reading_time`: minutes to read Markdown source, as shown on post pages
+struct ReadingTimeFilter {
+    markdown: Arc<MarkdownRenderer>,
+}
+
+impl tera::Filter for ReadingTimeFilter {
+    fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let source = expect_str(value, "reading_time")?;
+        Ok(Value::from(
+            self.markdown.render(source).reading_time_minutes,
+        ))
+    }
+}
+
+/// `pluralize(singular="post", plural="posts")`: the count followed by the
+/// matching word. `plural` defaults to `singular` with an `s`.
+fn pluralize(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let count = value
+        .as_f64()
+        .ok_or_else(|| tera::Error::msg("Filter `pluralize` expects a number"))?;
+    let singular = optional_str(args, "singular", "pluralize")?
+        .ok_or_else(|| tera::Error::msg("Filter `pluralize` requires a `singular` argument"))?;
+    let word = match optional_str(args, "plural", "pluralize")? {
+        _ if count == 1.0 => singular.to_string(),
+        Some(plural) => plural.to_string(),
+        None => format!("{}s", singular),
+    };
+    Ok(Value::String(format!("{} {}", value, word)))
+}
+
+/// `json_ld`: any value as a JSON-LD script element
+struct JsonLdFilter;
+
+impl tera::Filter for JsonLdFilter {
+    fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+        Ok(Value::String(script_tag(value)))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// `asset_url(path="js/main.js")`: the URL a static file is served from. Vite
+/// entries resolve to their hashed build output; other paths are served as is.
+struct AssetUrlFunction {
+    manifest: Option<HashMap<String, AssetInfo>>,
+    dev_server: Option<String>,
+}
+
+impl tera::Function for AssetUrlFunction {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let path = args
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or_else(|| tera::Error::msg("`asset_url` requires a `path` argument"))?
+            .trim_start_matches('/');
+
+        let url = match (&self.dev_server, &self.manifest) {
+            (Some(dev_server), _) => format!("{}{}{}", dev_server, BASE, path),
+            (None, Some(manifest)) => match manifest.get(path) {
+                Some(asset) => format!("{}{}", BASE, asset.file),
+                None => format!("{}{}", BASE, path),
+            },
+            (None, None) => format!("{}{}", BASE, path),
+        };
+        Ok(Value::String(url))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+fn expect_str<'a>(value: &'a Value, filter: &str) -> tera::Result<&'a str> {
+    value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg(format!("Filter `{}` expects a string", filter)))
+}
+
+fn optional_str<'a>(
+    args: &'a HashMap<String, Value>,
+    name: &str,
+    filter: &str,
+) -> tera::Result<Option<&'a str>> {
+    match args.get(name) {
+        None => Ok(None),
+        Some(value) => value.as_str().map(Some).ok_or_else(|| {
+            tera::Error::msg(format!("Filter `{}`: `{}` must be a string", filter, name))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, context: &tera::Context) -> String {
+        let manifest: HashMap<String, AssetInfo> = serde_json::from_str(
+            r#"{ "js/main.js": { "file": "js/main-AbC123xY.js", "isEntry": true } }"#,
+        )
+        .unwrap();
+        let mut tera = Tera::default();
+        register_site_filters(&mut tera, Arc::new(MarkdownRenderer::new()));
+        register_asset_url(&mut tera, Some(manifest), None);
+        tera.render_str(template, context).unwrap()
+    }
+
+    #[test]
+    fn test_date_formats_in_time_zone() {
+        let mut context = tera::Context::new();
+        context.insert("published", "2025-03-09T23:30:00Z");
+        context.insert("day", "2025-03-09");
+        context.insert("timestamp", &1741563000);
+
+        assert_eq!(
+            render(
+                "{{ published | date(format=\"%B %-d, %Y %H:%M\") }}",
+                &context
+            ),
+            "March 9, 2025 23:30"
+        );
+        assert_eq!(
+            render(
+                "{{ published | date(format=\"%Y-%m-%d %H:%M %Z\", tz=\"Europe/Berlin\") }}",
+                &context
+            ),
+            "2025-03-10 00:30 CET"
+        );
+        assert_eq!(
+            render(
+                "{{ day | date(format=\"%d/%m/%Y\", tz=\"America/New_York\") }}",
+                &context
+            ),
+            "09/03/2025"
+        );
+        assert_eq!(render("{{ timestamp | date }}", &context), "2025-03-09");
+    }
+
+    #[test]
+    fn test_truncate_words_cuts_at_word_boundary() {
+        let mut context = tera::Context::new();
+        context.insert("text", "Building  fast websites\nwith Rust and Axum");
+
+        assert_eq!(
+            render("{{ text | truncate_words(count=3) }}", &context),
+            "Building fast websites…"
+        );
+        assert_eq!(
+            render(
+                "{{ text | truncate_words(count=2, end=\" [more]\") }}",
+                &context
+            ),
+            "Building fast [more]"
+        );
+        assert_eq!(
+            render("{{ text | truncate_words(count=10) }}", &context),
+            "Building fast websites with Rust and Axum"
+        );
+    }
+
+    #[test]
+    fn test_slugify_matches_routes() {
+        let mut context = tera::Context::new();
+        context.insert("tag", "C++ & Rust");
+
+        assert_eq!(
+            render("{{ tag | slugify }}", &context),
+            slugify("C++ & Rust")
+        );
+        assert_eq!(render("{{ tag | slugify }}", &context), "c-rust");
+    }
+
+    #[test]
+    fn test_markdown_and_reading_time() {
+        let mut context = tera::Context::new();
+        context.insert("source", &format!("# Title\n\n{}", "word ".repeat(450)));
+
+        let html = render("{{ source | markdown }}", &context);
+        assert!(html.starts_with("<h1 id=\"title\">"), "{}", html);
+        assert_eq!(
+            render("{{ source | reading_time }} min read", &context),
+            "3 min read"
+        );
+    }
+
+    #[test]
+    fn test_pluralize() {
+        let context = tera::Context::new();
+
+        assert_eq!(
+            render("{{ 1 | pluralize(singular=\"post\") }}", &context),
+            "1 post"
+        );
+        assert_eq!(
+            render("{{ 0 | pluralize(singular=\"post\") }}", &context),
+            "0 posts"
+        );
+        assert_eq!(
+            render(
+                "{{ 3 | pluralize(singular=\"entry\", plural=\"entries\") }}",
+                &context
+            ),
+            "3 entries"
+        );
+    }
+
+    #[test]
+    fn test_json_ld_escapes_script_end() {
+        let mut context = tera::Context::new();
+        context.insert(
+            "data",
+            &serde_json::json!({ "@type": "Thing", "name": "</script><b>" }),
+        );
+
+        assert_eq!(
+            render("{{ data | json_ld }}", &context),
+            r#"<script type="application/ld+json">{"@type":"Thing","name":"\u003c/script>\u003cb>"}</script>"#
+        );
+    }
+
+    #[test]
+    fn test_asset_url_resolves_manifest_entries() {
+        let context = tera::Context::new();
+
+        assert_eq!(
+            render("{{ asset_url(path=\"js/main.js\") }}", &context),
+            "/static/js/main-AbC123xY.js"
+        );
+        assert_eq!(
+            render("{{ asset_url(path=\"/images/logo.png\") }}", &context),
+            "/static/images/logo.png"
+        );
+    }
+}
@@ -0,0 +1,439 @@
+// Uploaded images for blog content
+//
+// Uploads are identified by their bytes, never by the client's filename or
+// Content-Type, then decoded and re-encoded into lossy AVIF variants at several
+// widths, with a JPEG (or PNG, for transparency) fallback. The image crate can
+// only write lossless WebP, which is often larger than the upload, so no WebP
+// is generated. Re-encoding drops EXIF and every other metadata block, so
+// camera location data never reaches the public site. Variants live under a
+// content-addressed directory, so uploading the same file twice is a no-op.
+
+use anyhow::{Context as _, Result};
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{BufWriter, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Directory under the static root holding processed uploads
+pub const UPLOAD_DIR: &str = "uploads";
+
+/// Largest accepted upload
+pub const MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
+
+/// Responsive widths generated for each image (never upscaled)
+const VARIANT_WIDTHS: [u32; 3] = [480, 960, 1600];
+
+/// Decoding limits guarding against decompression bombs: pixels per side,
+/// and bytes for the decoded image (16 megapixels of RGBA)
+const MAX_DIMENSION: u32 = 8192;
+const MAX_DECODED_BYTES: u64 = 64 * 1024 * 1024;
+
+/// AVIF encoder speed (1 slowest - 10 fastest) and quality
+const AVIF_SPEED: u8 = 8;
+const AVIF_QUALITY: u8 = 70;
+const JPEG_QUALITY: u8 = 82;
+
+/// `sizes` attribute emitted with generated `srcset`s
+const PICTURE_SIZES: &str = "(max-width: 960px) 100vw, 960px";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImageError {
+    #[error("unsupported image format")]
+    UnsupportedFormat,
+    #[error("image exceeds the upload size limit")]
+    TooLarge,
+    #[error("could not decode image: {0}")]
+    Decode(String),
+    #[error(transparent)]
+    Storage(#[from] anyhow::Error),
+}
+
+/// Identify an image from its leading bytes
+pub fn sniff_format(bytes: &[u8]) -> Option<ImageFormat> {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => Some(ImageFormat::Jpeg),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(ImageFormat::Png),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(ImageFormat::Gif),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+/// A processed upload and the variants generated for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredImage {
+    pub hash: String,
+    pub width: u32,
+    pub height: u32,
+    /// Widths available as AVIF, ascending
+    pub widths: Vec<u32>,
+    /// Largest variant in a universally supported format, for `<img src>`
+    pub fallback: String,
+}
+
+impl StoredImage {
+    fn base_url(&self) -> String {
+        format!("/static/{}/{}/{}", UPLOAD_DIR, &self.hash[..2], self.hash)
+    }
+
+    pub fn fallback_url(&self) -> String {
+        format!("{}/{}", self.base_url(), self.fallback)
+    }
+
+    pub fn srcset(&self, extension: &str) -> String {
+        let base = self.base_url();
+        self.widths
+            .iter()
+            .map(|width| format!("{}/{}.{} {}w", base, width, extension, width))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Dimensions of the fallback variant
+    pub fn display_size(&self) -> (u32, u32) {
+        let width = self.widths.last().copied().unwrap_or(self.width);
+        (width, scaled_height(self.width, self.height, width))
+    }
+
+    /// `<picture>` element offering AVIF, then the fallback
+    pub fn picture_html(&self, alt: &str, title: Option<&str>) -> String {
+        let (width, height) = self.display_size();
+        let title = title
+            .filter(|title| !title.is_empty())
+            .map(|title| format!(" title=\"{}\"", attr_escape(title)))
+            .unwrap_or_default();
+        format!(
+            "<picture>\
+             <source type=\"image/avif\" srcset=\"{avif}\" sizes=\"{sizes}\">\
+             <img src=\"{src}\" alt=\"{alt}\"{title} width=\"{width}\" height=\"{height}\" loading=\"lazy\" decoding=\"async\">\
+             </picture>",
+            avif = self.srcset("avif"),
+            sizes = PICTURE_SIZES,
+            src = self.fallback_url(),
+            alt = attr_escape(alt),
+        )
+    }
+}
+
+pub struct ImageStore {
+    root: PathBuf,
+    images: RwLock<HashMap<String, StoredImage>>,
+    /// Bumped with every new upload, so renders that resolved image URLs
+    /// against an older set of uploads can be told apart
+    generation: AtomicU64,
+}
+
+impl ImageStore {
+    /// Open the upload store under `static_dir`, indexing earlier uploads
+    pub fn open(static_dir: impl AsRef<Path>) -> Result<Self> {
+        let root = static_dir.as_ref().join(UPLOAD_DIR);
+        let mut images = HashMap::new();
+
+        if root.is_dir() {
+            for shard in std::fs::read_dir(&root)? {
+                let shard = shard?.path();
+                if !shard.is_dir() {
+                    continue;
+                }
+                for entry in std::fs::read_dir(&shard)? {
+                    let meta_path = entry?.path().join("meta.json");
+                    let Ok(raw) = std::fs::read_to_string(&meta_path) else {
+                        continue;
+                    };
+                    match serde_json::from_str::<StoredImage>(&raw) {
+                        Ok(image) => {
+                            images.insert(image.hash.clone(), image);
+                        }
+                        Err(err) => {
+                            tracing::warn!("Skipping {}: {}", meta_path.display(), err)
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            root,
+            images: RwLock::new(images),
+            generation: AtomicU64::new(0),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn get(&self, hash: &str) -> Option<StoredImage> {
+        self.images.read().unwrap().get(hash).cloned()
+    }
+
+    /// Uploaded image referenced by any of its public URLs
+    pub fn resolve_url(&self, url: &str) -> Option<StoredImage> {
+        let rest = url.strip_prefix(&format!("/static/{}/", UPLOAD_DIR))?;
+        let mut segments = rest.split('/');
+        let _shard = segments.next()?;
+        self.get(segments.next()?)
+    }
+
+    /// Validate, process and store an upload. Encoding runs on the blocking
+    /// pool since AVIF in particular is CPU heavy.
+    pub async fn store(self: &Arc<Self>, bytes: Vec<u8>) -> Result<StoredImage, ImageError> {
+        if bytes.len() > MAX_UPLOAD_BYTES {
+            return Err(ImageError::TooLarge);
+        }
+        let format = sniff_format(&bytes).ok_or(ImageError::UnsupportedFormat)?;
+        let hash = hex::encode(&Sha256::digest(&bytes)[..16]);
+
+        if let Some(existing) = self.get(&hash) {
+            return Ok(existing);
+        }
+
+        let store = self.clone();
+        let image = tokio::task::spawn_blocking(move || store.process(&bytes, format, hash))
+            .await
+            .map_err(|err| ImageError::Storage(err.into()))??;
+
+        self.images
+            .write()
+            .unwrap()
+            .insert(image.hash.clone(), image.clone());
+        self.generation.fetch_add(1, Ordering::Release);
+        Ok(image)
+    }
+
+    fn process(
+        &self,
+        bytes: &[u8],
+        format: ImageFormat,
+        hash: String,
+    ) -> Result<StoredImage, ImageError> {
+        let image = decode(bytes, format)?;
+        let (width, height) = (image.width(), image.height());
+
+        let mut widths: Vec<u32> = VARIANT_WIDTHS
+            .iter()
+            .copied()
+            .filter(|variant| *variant < width)
+            .collect();
+        if width <= VARIANT_WIDTHS[VARIANT_WIDTHS.len() - 1] {
+            widths.push(width);
+        }
+
+        let largest = *widths.last().expect("at least one variant width");
+        let dir = self.root.join(&hash[..2]).join(&hash);
+        let staging =
+            self.root
+                .join(&hash[..2])
+                .join(format!(".{}.{}", hash, uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&staging)
+            .with_context(|| format!("creating {}", staging.display()))?;
+
+        let result = (|| -> Result<String> {
+            let mut fallback = String::new();
+            for &variant_width in &widths {
+                let resized = if variant_width == width {
+                    image.clone()
+                } else {
+                    image.resize_exact(
+                        variant_width,
+                        scaled_height(width, height, variant_width),
+                        FilterType::Lanczos3,
+                    )
+                };
+                let rgba = DynamicImage::ImageRgba8(resized.to_rgba8());
+
+                encode(&staging.join(format!("{}.avif", variant_width)), |writer| {
+                    rgba.write_with_encoder(AvifEncoder::new_with_speed_quality(
+                        writer,
+                        AVIF_SPEED,
+                        AVIF_QUALITY,
+                    ))
+                })?;
+
+                if variant_width != largest {
+                    continue;
+                }
+                // Fallback keeps transparency when the source has any
+                fallback = if resized.color().has_alpha() {
+                    let name = format!("{}.png", variant_width);
+                    encode(&staging.join(&name), |writer| {
+                        rgba.write_with_encoder(PngEncoder::new(writer))
+                    })?;
+                    name
+                } else {
+                    let name = format!("{}.jpg", variant_width);
+                    let rgb = DynamicImage::ImageRgb8(resized.to_rgb8());
+                    encode(&staging.join(&name), |writer| {
+                        rgb.write_with_encoder(JpegEncoder::new_with_quality(writer, JPEG_QUALITY))
+                    })?;
+                    name
+                };
+            }
+            Ok(fallback)
+        })();
+
+        let fallback = match result {
+            Ok(result) => result,
+            Err(err) => {
+                let _ = std::fs::remove_dir_all(&staging);
+                return Err(err.into());
+            }
+        };
+
+        let stored = StoredImage {
+            hash,
+            width,
+            height,
+            widths,
+            fallback,
+        };
+        std::fs::write(
+            staging.join("meta.json"),
+            serde_json::to_vec_pretty(&stored).map_err(anyhow::Error::from)?,
+        )
+        .map_err(anyhow::Error::from)?;
+
+        // A concurrent upload of the same bytes may have won the race
+        if std::fs::rename(&staging, &dir).is_err() {
+            let _ = std::fs::remove_dir_all(&staging);
+            if !dir.join("meta.json").is_file() {
+                return Err(anyhow::anyhow!("could not store {}", dir.display()).into());
+            }
+        }
+
+        Ok(stored)
+    }
+}
+
+fn decode(bytes: &[u8], format: ImageFormat) -> Result<DynamicImage, ImageError> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODED_BYTES);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits.clone());
+    let mut decoder = reader.into_decoder().map_err(decode_error)?;
+    // Most decoders leave `max_alloc` to the caller
+    limits
+        .reserve(decoder.total_bytes())
+        .map_err(decode_error)?;
+
+    // Honour the EXIF orientation before the metadata is discarded
+    let orientation = decoder.orientation().map_err(decode_error)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+fn decode_error(err: image::ImageError) -> ImageError {
+    match err {
+        image::ImageError::Limits(_) => ImageError::TooLarge,
+        err => ImageError::Decode(err.to_string()),
+    }
+}
+
+fn encode(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<std::fs::File>) -> image::ImageResult<()>,
+) -> Result<()> {
+    let mut writer = BufWriter::new(std::fs::File::create(path)?);
+    write(&mut writer).with_context(|| format!("encoding {}", path.display()))?;
+    Ok(())
+}
+
+fn scaled_height(width: u32, height: u32, target_width: u32) -> u32 {
+    ((height as u64 * target_width as u64) / width.max(1) as u64).max(1) as u32
+}
+
+fn attr_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_format_uses_magic_bytes() {
+        assert_eq!(
+            sniff_format(b"\xFF\xD8\xFF\xE0rest"),
+            Some(ImageFormat::Jpeg)
+        );
+        assert_eq!(sniff_format(b"GIF89a...."), Some(ImageFormat::Gif));
+        assert_eq!(
+            sniff_format(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some(ImageFormat::WebP)
+        );
+        assert_eq!(sniff_format(b"<svg xmlns="), None);
+        assert_eq!(sniff_format(b"RIFF\0\0\0\0WAVEfmt "), None);
+    }
+
+    #[tokio::test]
+    async fn test_store_generates_variants_and_dedupes() {
+        let dir = std::env::temp_dir().join(format!("images-{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(ImageStore::open(&dir).unwrap());
+
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(600, 300)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+
+        let image = store.store(png.clone()).await.unwrap();
+        assert_eq!(image.widths, vec![480, 600]);
+        assert_eq!(image.fallback, "600.jpg");
+        assert_eq!(image.display_size(), (600, 300));
+        let html = image.picture_html("A \"quote\"", None);
+        assert!(html.contains("alt=\"A &quot;quote&quot;\""));
+        assert!(html.contains("600.avif 600w"));
+        assert!(!html.contains("image/webp"));
+        assert!(store.resolve_url(&image.fallback_url()).is_some());
+
+        let again = store.store(png).await.unwrap();
+        assert_eq!(again.hash, image.hash);
+        assert_eq!(ImageStore::open(&dir).unwrap().len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_decode_rejects_images_too_large_to_hold_in_memory() {
+        // A 6000x6000 RGBA header; only the header is read before the check
+        let mut ihdr = b"IHDR".to_vec();
+        ihdr.extend(6000u32.to_be_bytes());
+        ihdr.extend(6000u32.to_be_bytes());
+        ihdr.extend([8, 6, 0, 0, 0]);
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        for chunk in [ihdr, b"IDAT".to_vec()] {
+            let mut crc = flate2::Crc::new();
+            crc.update(&chunk);
+            png.extend((chunk.len() as u32 - 4).to_be_bytes());
+            png.extend(&chunk);
+            png.extend(crc.sum().to_be_bytes());
+        }
+
+        assert!(matches!(
+            decode(&png, ImageFormat::Png),
+            Err(ImageError::TooLarge)
+        ));
+    }
+}
@@ -0,0 +1,154 @@
+// Startup verification of the Vite manifest
+//
+// A deploy that ships a manifest without the files it references serves pages
+// with broken JS and CSS. At startup every file the manifest names (entry
+// files, imported chunks, CSS and assets) is read once and its size and hash
+// recorded; anything missing fails the readiness check.
+
+use crate::services::assets::{read_manifest, AssetInfo};
+use crate::services::embedded::AssetSource;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+
+/// A manifest file found in the static tree
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifiedAsset {
+    pub file: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestStatus {
+    /// Every referenced file is present
+    Ok,
+    /// No manifest: the frontend hasn't been built and default paths are used
+    NotBuilt,
+    /// The manifest could not be parsed
+    Invalid(String),
+    /// Referenced files are missing or unreadable
+    MissingFiles,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestCheck {
+    pub status: ManifestStatus,
+    pub verified: Vec<VerifiedAsset>,
+    pub missing: Vec<String>,
+}
+
+impl ManifestCheck {
+    pub fn run(source: &AssetSource) -> Self {
+        match read_manifest(source) {
+            Ok(Some(manifest)) => Self::verify(source, &manifest),
+            Ok(None) => Self {
+                status: ManifestStatus::NotBuilt,
+                verified: Vec::new(),
+                missing: Vec::new(),
+            },
+            Err(e) => Self {
+                status: ManifestStatus::Invalid(e.to_string()),
+                verified: Vec::new(),
+                missing: Vec::new(),
+            },
+        }
+    }
+
+    fn verify(source: &AssetSource, manifest: &HashMap<String, AssetInfo>) -> Self {
+        let mut verified = Vec::new();
+        let mut missing = Vec::new();
+        for file in referenced_files(manifest) {
+            match source.read_static(&file) {
+                Some(contents) => verified.push(VerifiedAsset {
+                    size: contents.len() as u64,
+                    sha256: hex::encode(Sha256::digest(&contents)),
+                    file,
+                }),
+                None => missing.push(file),
+            }
+        }
+
+        let status = if missing.is_empty() {
+            ManifestStatus::Ok
+        } else {
+            ManifestStatus::MissingFiles
+        };
+        Self {
+            status,
+            verified,
+            missing,
+        }
+    }
+
+    /// Whether pages can be served with the assets they reference
+    pub fn is_ready(&self) -> bool {
+        matches!(self.status, ManifestStatus::Ok | ManifestStatus::NotBuilt)
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.verified.iter().map(|asset| asset.size).sum()
+    }
+}
+
+/// Every file the manifest refers to, relative to the static root. Imports
+/// name other manifest keys, so they are covered by those keys' files.
+fn referenced_files(manifest: &HashMap<String, AssetInfo>) -> BTreeSet<String> {
+    let mut files = BTreeSet::new();
+    for asset in manifest.values() {
+        files.insert(asset.file.clone());
+        files.extend(asset.css.iter().flatten().cloned());
+        files.extend(asset.assets.iter().flatten().cloned());
+        for import in asset.imports.iter().flatten() {
+            match manifest.get(import) {
+                Some(imported) => files.insert(imported.file.clone()),
+                // Unknown keys are reported as missing files
+                None => files.insert(import.clone()),
+            };
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+
+    #[test]
+    fn test_missing_manifest_files_fail_readiness() {
+        let dir = std::env::temp_dir().join(format!("manifest-check-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join(".vite")).unwrap();
+        std::fs::create_dir_all(dir.join("js")).unwrap();
+        std::fs::write(
+            dir.join(".vite/manifest.json"),
+            r#"{
+                "js/main.js": {
+                    "file": "js/main-AbC123xY.js",
+                    "isEntry": true,
+                    "imports": ["_core-Dq3kLm9x.js"],
+                    "css": ["css/main-Zz9Yy8Xx.css"]
+                },
+                "_core-Dq3kLm9x.js": { "file": "js/core-Dq3kLm9x.js" }
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("js/main-AbC123xY.js"), "main()").unwrap();
+        std::fs::write(dir.join("js/core-Dq3kLm9x.js"), "core()").unwrap();
+
+        let mut config = AppConfig::from_env();
+        config.static_dir = dir.to_string_lossy().into_owned();
+        let check = ManifestCheck::run(&AssetSource::new(&config));
+        assert!(!check.is_ready());
+        assert_eq!(check.missing, vec!["css/main-Zz9Yy8Xx.css"]);
+        assert_eq!(check.verified.len(), 2);
+        assert_eq!(check.total_bytes(), 12);
+
+        std::fs::create_dir_all(dir.join("css")).unwrap();
+        std::fs::write(dir.join("css/main-Zz9Yy8Xx.css"), "body {}").unwrap();
+        assert!(ManifestCheck::run(&AssetSource::new(&config)).is_ready());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,497 @@
+// Markdown rendering service for blog content
+//
+// Converts CommonMark + GFM (tables, task lists, footnotes, strikethrough) into
+// sanitized HTML with server-side syntax highlighting, heading anchors, a table
+// of contents and a reading-time estimate. Rendered output is cached by content
+// hash so repeated renders of the same post are free. Images that point at
+// uploads become responsive `<picture>` elements, so the cache key also carries
+// the upload generation.
+
+use crate::services::images::{ImageStore, StoredImage};
+use crate::utils::slugify;
+use lru::LruCache;
+use pulldown_cmark::{html, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// Average adult silent reading speed used for the reading-time estimate
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Maximum number of rendered documents kept in memory
+const CACHE_CAPACITY: usize = 256;
+
+/// CSS class prefix for highlighted tokens, matched by `highlight_css`
+const HIGHLIGHT_CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// Theme used to generate the syntax highlighting stylesheet
+const HIGHLIGHT_THEME: &str = "base16-ocean.dark";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TocEntry {
+    pub level: u8,
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+    pub word_count: usize,
+    pub reading_time_minutes: usize,
+}
+
+impl RenderedMarkdown {
+    /// Render the table of contents as a nested list of anchor links
+    pub fn toc_html(&self) -> String {
+        if self.toc.is_empty() {
+            return String::new();
+        }
+
+        let base_level = self.toc.iter().map(|entry| entry.level).min().unwrap_or(1);
+        let mut out = String::from("<nav class=\"toc\"><ul>");
+        let mut depth = 0usize;
+
+        for (index, entry) in self.toc.iter().enumerate() {
+            let level = (entry.level - base_level) as usize;
+            if index > 0 {
+                if level > depth {
+                    for _ in depth..level {
+                        out.push_str("<ul>");
+                    }
+                } else {
+                    out.push_str("</li>");
+                    for _ in level..depth {
+                        out.push_str("</ul></li>");
+                    }
+                }
+            }
+            depth = level;
+            out.push_str(&format!(
+                "<li><a href=\"#{}\">{}</a>",
+                entry.id,
+                html_escape(&entry.title)
+            ));
+        }
+
+        out.push_str("</li>");
+        for _ in 0..depth {
+            out.push_str("</ul></li>");
+        }
+        out.push_str("</ul></nav>");
+        out
+    }
+}
+
+pub struct MarkdownRenderer {
+    syntax_set: SyntaxSet,
+    sanitizer: ammonia::Builder<'static>,
+    cache: Mutex<LruCache<String, Arc<RenderedMarkdown>>>,
+    images: Option<Arc<ImageStore>>,
+    /// Stylesheet for the highlighter's classes, generated once
+    highlight_css: String,
+}
+
+impl Default for MarkdownRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MarkdownRenderer {
+    pub fn new() -> Self {
+        Self {
+            syntax_set: SyntaxSet::load_defaults_newlines(),
+            sanitizer: build_sanitizer(),
+            cache: Mutex::new(LruCache::new(
+                NonZeroUsize::new(CACHE_CAPACITY).expect("cache capacity must be non-zero"),
+            )),
+            images: None,
+            highlight_css: build_highlight_css(),
+        }
+    }
+
+    /// Render references to uploaded images as `<picture>` with `srcset`s
+    pub fn with_images(mut self, images: Arc<ImageStore>) -> Self {
+        self.images = Some(images);
+        self
+    }
+
+    /// Render Markdown to sanitized HTML, reusing a cached result when the
+    /// same content has been rendered before with the same uploads
+    pub fn render(&self, source: &str) -> Arc<RenderedMarkdown> {
+        // A new upload can turn a plain image in existing content into a
+        // `<picture>`
+        let generation = self.images.as_ref().map_or(0, |images| images.generation());
+        let key = format!("{}:{}", content_hash(source), generation);
+
+        if let Some(hit) = self.cache.lock().unwrap().get(&key) {
+            return hit.clone();
+        }
+
+        let rendered = Arc::new(self.render_uncached(source));
+        self.cache.lock().unwrap().put(key, rendered.clone());
+        rendered
+    }
+
+    /// Stylesheet for the classes emitted by the syntax highlighter
+    pub fn highlight_css(&self) -> &str {
+        &self.highlight_css
+    }
+
+    fn render_uncached(&self, source: &str) -> RenderedMarkdown {
+        let parser = Parser::new_ext(source, markdown_options());
+
+        let mut events: Vec<Event> = Vec::new();
+        let mut toc = Vec::new();
+        let mut used_ids = HashSet::new();
+        let mut word_count = 0usize;
+
+        // Heading state: buffered inner events and plain text for the anchor id
+        let mut heading: Option<(HeadingLevel, Vec<Event>, String)> = None;
+        // Code block state: language and accumulated source
+        let mut code_block: Option<(Option<String>, String)> = None;
+
+        for event in parser {
+            match event {
+                Event::Start(Tag::Heading { level, .. }) => {
+                    heading = Some((level, Vec::new(), String::new()));
+                }
+                Event::End(TagEnd::Heading(_)) => {
+                    if let Some((level, inner, text)) = heading.take() {
+                        let id = unique_id(&text, &mut used_ids);
+                        let level_num = heading_level_number(level);
+                        toc.push(TocEntry {
+                            level: level_num,
+                            id: id.clone(),
+                            title: text.trim().to_string(),
+                        });
+                        events.push(Event::Html(
+                            format!("<h{} id=\"{}\">", level_num, id).into(),
+                        ));
+                        events.extend(inner);
+                        events.push(Event::Html(
+                            format!(
+                                "<a class=\"heading-anchor\" href=\"#{}\" aria-hidden=\"true\">#</a></h{}>\n",
+                                id, level_num
+                            )
+                            .into(),
+                        ));
+                    }
+                }
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let lang = match kind {
+                        CodeBlockKind::Fenced(info) => {
+                            info.split_whitespace().next().map(|lang| lang.to_string())
+                        }
+                        CodeBlockKind::Indented => None,
+                    };
+                    code_block = Some((lang, String::new()));
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    if let Some((lang, code)) = code_block.take() {
+                        word_count += count_words(&code);
+                        events.push(Event::Html(self.highlight(&code, lang.as_deref()).into()));
+                    }
+                }
+                Event::Text(text) if code_block.is_some() => {
+                    if let Some((_, code)) = code_block.as_mut() {
+                        code.push_str(&text);
+                    }
+                }
+                other => {
+                    if let Event::Text(text) | Event::Code(text) = &other {
+                        word_count += count_words(text);
+                        if let Some((_, _, heading_text)) = heading.as_mut() {
+                            heading_text.push_str(text);
+                        }
+                    }
+                    match heading.as_mut() {
+                        Some((_, inner, _)) => inner.push(other),
+                        None => events.push(other),
+                    }
+                }
+            }
+        }
+
+        let events = match &self.images {
+            Some(images) => replace_uploaded_images(events, images),
+            None => events,
+        };
+
+        let mut unsafe_html = String::with_capacity(source.len() * 3 / 2);
+        html::push_html(&mut unsafe_html, events.into_iter());
+
+        RenderedMarkdown {
+            html: self.sanitizer.clean(&unsafe_html).to_string(),
+            toc,
+            word_count,
+            reading_time_minutes: word_count.div_ceil(WORDS_PER_MINUTE).max(1),
+        }
+    }
+
+    fn highlight(&self, code: &str, lang: Option<&str>) -> String {
+        let syntax = lang
+            .and_then(|lang| self.syntax_set.find_syntax_by_token(lang))
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+
+        let mut generator = ClassedHTMLGenerator::new_with_class_style(
+            syntax,
+            &self.syntax_set,
+            HIGHLIGHT_CLASS_STYLE,
+        );
+        for line in LinesWithEndings::from(code) {
+            if generator
+                .parse_html_for_line_which_includes_newline(line)
+                .is_err()
+            {
+                // Fall back to an unhighlighted block if the grammar chokes
+                return format!("<pre><code>{}</code></pre>\n", html_escape(code));
+            }
+        }
+
+        let class = lang
+            .map(|lang| format!(" class=\"language-{}\"", html_escape(lang)))
+            .unwrap_or_default();
+        format!(
+            "<pre class=\"highlight\"><code{}>{}</code></pre>\n",
+            class,
+            generator.finalize()
+        )
+    }
+}
+
+/// Extract the readable text from Markdown, dropping markup, for indexing
+/// and plain-text summaries
+pub fn plain_text(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    for event in Parser::new_ext(source, markdown_options()) {
+        match event {
+            Event::Text(text) | Event::Code(text) => out.push_str(&text),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) if !out.ends_with(' ') => {
+                out.push(' ');
+            }
+            _ => {}
+        }
+    }
+    out.trim().to_string()
+}
+
+/// Tera filter: `{{ post.content | markdown }}`, or `markdown(toc=true)` to
+/// prepend the generated table of contents
+pub struct MarkdownFilter {
+    renderer: Arc<MarkdownRenderer>,
+}
+
+impl MarkdownFilter {
+    pub fn new(renderer: Arc<MarkdownRenderer>) -> Self {
+        Self { renderer }
+    }
+}
+
+impl tera::Filter for MarkdownFilter {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let source = value
+            .as_str()
+            .ok_or_else(|| tera::Error::msg("Filter `markdown` expects a string"))?;
+        let rendered = self.renderer.render(source);
+
+        let with_toc = args.get("toc").and_then(|v| v.as_bool()).unwrap_or(false);
+        let html = if with_toc {
+            format!("{}{}", rendered.toc_html(), rendered.html)
+        } else {
+            rendered.html.clone()
+        };
+
+        Ok(tera::Value::String(html))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Swap `![alt](/static/uploads/...)` images for responsive `<picture>` markup
+fn replace_uploaded_images<'a>(events: Vec<Event<'a>>, images: &ImageStore) -> Vec<Event<'a>> {
+    let mut out = Vec::with_capacity(events.len());
+    // Image being replaced: stored variants, title and accumulated alt text
+    let mut picture: Option<(StoredImage, String, String)> = None;
+
+    for event in events {
+        if let Some((_, _, alt)) = picture.as_mut() {
+            match event {
+                Event::End(TagEnd::Image) => {
+                    let (image, title, alt) = picture.take().expect("picture in progress");
+                    out.push(Event::Html(image.picture_html(&alt, Some(&title)).into()));
+                }
+                Event::Text(text) | Event::Code(text) => alt.push_str(&text),
+                _ => {}
+            }
+            continue;
+        }
+
+        if let Event::Start(Tag::Image {
+            dest_url, title, ..
+        }) = &event
+        {
+            if let Some(image) = images.resolve_url(dest_url) {
+                picture = Some((image, title.to_string(), String::new()));
+                continue;
+            }
+        }
+        out.push(event);
+    }
+    out
+}
+
+fn markdown_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+}
+
+fn build_highlight_css() -> String {
+    let themes = ThemeSet::load_defaults();
+    themes
+        .themes
+        .get(HIGHLIGHT_THEME)
+        .and_then(|theme| css_for_theme_with_class_style(theme, HIGHLIGHT_CLASS_STYLE).ok())
+        .unwrap_or_default()
+}
+
+fn build_sanitizer() -> ammonia::Builder<'static> {
+    let mut builder = ammonia::Builder::default();
+    builder
+        .add_tags(&["input", "picture", "source"])
+        .add_tag_attributes("input", &["type", "checked", "disabled"])
+        .add_tag_attributes("source", &["type", "srcset", "sizes"])
+        .add_tag_attributes("img", &["loading", "decoding"])
+        .add_tag_attributes("a", &["class", "aria-hidden"])
+        .add_tag_attributes("pre", &["class"])
+        .add_tag_attributes("code", &["class"])
+        .add_tag_attributes("span", &["class"])
+        .add_tag_attributes("sup", &["class"])
+        .add_tag_attributes("div", &["class", "id"]);
+    for heading in ["h1", "h2", "h3", "h4", "h5", "h6"] {
+        builder.add_tag_attributes(heading, &["id"]);
+    }
+    builder
+}
+
+fn content_hash(source: &str) -> String {
+    hex::encode(Sha256::digest(source.as_bytes()))
+}
+
+fn unique_id(text: &str, used: &mut HashSet<String>) -> String {
+    let base = match slugify(text) {
+        slug if slug.is_empty() => "section".to_string(),
+        slug => slug,
+    };
+
+    let mut id = base.clone();
+    let mut suffix = 1;
+    while !used.insert(id.clone()) {
+        id = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+    id
+}
+
+fn heading_level_number(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+fn count_words(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headings_get_anchors_and_toc() {
+        let renderer = MarkdownRenderer::new();
+        let rendered = renderer.render("# Intro\n\n## Setup\n\n## Setup\n");
+
+        assert!(rendered.html.contains("<h1 id=\"intro\">"));
+        assert!(rendered.html.contains("<h2 id=\"setup-1\">"));
+        assert_eq!(rendered.toc.len(), 3);
+        assert!(rendered.toc_html().contains("href=\"#setup\""));
+    }
+
+    #[test]
+    fn test_raw_html_is_sanitized() {
+        let renderer = MarkdownRenderer::new();
+        let rendered = renderer.render("Hello <script>alert(1)</script> **world**");
+
+        assert!(!rendered.html.contains("<script>"));
+        assert!(rendered.html.contains("<strong>world</strong>"));
+    }
+
+    #[test]
+    fn test_code_blocks_are_highlighted() {
+        let renderer = MarkdownRenderer::new();
+        let rendered = renderer.render("```rust\nfn main() {}\n```\n");
+
+        assert!(rendered.html.contains("class=\"language-rust\""));
+        assert!(rendered.html.contains("hl-"));
+        assert!(renderer.highlight_css().contains(".hl-"));
+    }
+
+    #[test]
+    fn test_reading_time_rounds_up() {
+        let renderer = MarkdownRenderer::new();
+        let words = "word ".repeat(450);
+
+        assert_eq!(renderer.render(&words).reading_time_minutes, 3);
+        assert_eq!(renderer.render("short").reading_time_minutes, 1);
+    }
+
+    #[tokio::test]
+    async fn test_new_upload_invalidates_cached_renders() {
+        let dir = std::env::temp_dir().join(format!("markdown-{}", uuid::Uuid::new_v4()));
+        let images = Arc::new(ImageStore::open(&dir).unwrap());
+        let renderer = MarkdownRenderer::new().with_images(images.clone());
+
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(100, 50)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        // Content can reference an upload's URL before the upload lands
+        let hash = hex::encode(&Sha256::digest(&png)[..16]);
+        let source = format!("![Chart](/static/uploads/{}/{}/100.jpg)", &hash[..2], hash);
+
+        assert!(!renderer.render(&source).html.contains("<picture>"));
+        images.store(png).await.unwrap();
+        assert!(renderer.render(&source).html.contains("<picture>"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
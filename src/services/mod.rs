@@ -1,7 +1,42 @@
+pub mod asset_gc;
 pub mod assets;
+pub mod cache_policy;
 pub mod database;
+pub mod embedded;
+pub mod feeds;
+pub mod filters;
+pub mod images;
+pub mod manifest_check;
+pub mod markdown;
+pub mod og_images;
+pub mod page_cache;
+pub mod precompress;
+pub mod preload;
+pub mod preview;
+pub mod ranges;
+pub mod redirects;
+pub mod revisions;
+pub mod safe_path;
+pub mod search;
+pub mod session;
+pub mod sitemap;
+pub mod static_cache;
+pub mod structured_data;
 pub mod template;
+pub mod vite;
 
 pub use assets::*;
+pub use cache_policy::CachePolicy;
 pub use database::*;
+pub use embedded::AssetSource;
+pub use feeds::{Feed, FeedEntry};
+pub use images::{ImageStore, StoredImage};
+pub use markdown::{MarkdownRenderer, RenderedMarkdown, TocEntry};
+pub use og_images::{OgCard, OgImageRenderer, SocialCards, SocialMeta};
+pub use page_cache::{PageCache, PageCacheStats};
+pub use preload::PreloadLinks;
+pub use revisions::{RevisionDiff, RevisionRetention};
+pub use search::{SearchIndex, SearchResult};
+pub use sitemap::SitemapPages;
+pub use static_cache::{StaticCache, StaticCacheStats};
 pub use template::*;
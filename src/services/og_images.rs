@@ -0,0 +1,513 @@
+// Open Graph preview cards
+//
+// Renders 1200x630 PNG cards (title, tags, author, site logo) set in Inter for
+// link previews on social platforms. Cards are cached on disk under a hash of
+// their content, so a card is only re-rendered when what it shows changes.
+
+use crate::models::{BlogPost, CVData};
+use crate::services::embedded::AssetSource;
+use ab_glyph::{point, Font, FontVec, GlyphId, PxScale, ScaleFont, VariableFont};
+use anyhow::{Context as _, Result};
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, Rgba, RgbaImage};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub const OG_WIDTH: u32 = 1200;
+pub const OG_HEIGHT: u32 = 630;
+pub const HOME_CARD_PATH: &str = "/og/home.png";
+
+/// Bump when the card design changes so cached cards are regenerated
+const DESIGN_VERSION: u32 = 1;
+
+const FONT_PATH: &str = "fonts/inter-variable.ttf";
+const LOGO_PATH: &str = "images/logo.png";
+const MARGIN: f32 = 80.0;
+const LOGO_SIZE: u32 = 88;
+const BACKGROUND_TOP: [u8; 3] = [15, 23, 42];
+const BACKGROUND_BOTTOM: [u8; 3] = [49, 46, 129];
+const ACCENT: [u8; 3] = [99, 102, 241];
+const TAG_COLOR: [u8; 3] = [165, 180, 252];
+const TEXT_COLOR: [u8; 3] = [248, 250, 252];
+const MUTED_COLOR: [u8; 3] = [148, 163, 184];
+
+/// What a card shows; its hash is the cache key and the URL cache-buster
+#[derive(Debug, Clone, Serialize)]
+pub struct OgCard {
+    pub title: String,
+    pub tags: Vec<String>,
+    pub author: String,
+}
+
+impl OgCard {
+    pub fn for_post(post: &BlogPost, author: &str) -> Self {
+        Self {
+            title: post.title.clone(),
+            tags: post.tags.clone(),
+            author: author.to_string(),
+        }
+    }
+
+    /// Home page card: headline title and strongest skills
+    pub fn for_home(cv_data: &CVData) -> Self {
+        let mut skills: Vec<_> = cv_data.skills.iter().collect();
+        skills.sort_by_key(|skill| std::cmp::Reverse(skill.level));
+        Self {
+            title: cv_data.personal_info.title.clone(),
+            tags: skills
+                .iter()
+                .take(4)
+                .map(|skill| skill.name.clone())
+                .collect(),
+            author: cv_data.personal_info.name.clone(),
+        }
+    }
+
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(DESIGN_VERSION.to_be_bytes());
+        hasher.update(serde_json::to_vec(self).unwrap_or_default());
+        hex::encode(&hasher.finalize()[..8])
+    }
+}
+
+/// Link preview metadata rendered into `og:` and `twitter:` tags
+#[derive(Debug, Clone, Serialize)]
+pub struct SocialMeta {
+    pub title: String,
+    pub description: String,
+    pub url: String,
+    /// Card URL; `None` when cards can't be rendered
+    pub image: Option<String>,
+    pub image_width: u32,
+    pub image_height: u32,
+    /// `og:type`, e.g. `website` or `article`
+    pub kind: &'static str,
+    pub site_name: String,
+}
+
+impl SocialMeta {
+    pub fn for_home(site_url: &str, cv_data: &CVData) -> Self {
+        let info = &cv_data.personal_info;
+        Self::new(
+            site_url,
+            "/",
+            format!("{} - {}", info.name, info.title),
+            summarize(&info.summary, 200),
+            &format!("{}?v={}", HOME_CARD_PATH, OgCard::for_home(cv_data).hash()),
+            "website",
+            &info.name,
+        )
+    }
+
+    pub fn for_post(site_url: &str, post: &BlogPost, cv_data: &CVData) -> Self {
+        let author = &cv_data.personal_info.name;
+        Self::new(
+            site_url,
+            &format!("/blog/{}", post.slug),
+            post.title.clone(),
+            post.excerpt.clone().unwrap_or_default(),
+            &format!(
+                "{}?v={}",
+                post_card_path(&post.slug),
+                OgCard::for_post(post, author).hash()
+            ),
+            "article",
+            author,
+        )
+    }
+
+    fn new(
+        site_url: &str,
+        path: &str,
+        title: String,
+        description: String,
+        image_path: &str,
+        kind: &'static str,
+        site_name: &str,
+    ) -> Self {
+        Self {
+            title,
+            description,
+            url: format!("{}{}", site_url, path),
+            image: Some(format!("{}{}", site_url, image_path)),
+            image_width: OG_WIDTH,
+            image_height: OG_HEIGHT,
+            kind,
+            site_name: site_name.to_string(),
+        }
+    }
+}
+
+/// The card renderer, if it could be set up, and the meta tags pointing at
+/// its cards. Without a renderer the site still runs, minus the card images.
+pub struct SocialCards {
+    site_url: String,
+    renderer: Option<Arc<OgImageRenderer>>,
+}
+
+impl SocialCards {
+    pub fn new(source: &AssetSource, cache_dir: impl Into<PathBuf>, site_url: &str) -> Self {
+        let renderer = match OgImageRenderer::new(source, cache_dir, site_url) {
+            Ok(renderer) => Some(Arc::new(renderer)),
+            Err(err) => {
+                tracing::error!("Open Graph cards disabled: {:#}", err);
+                None
+            }
+        };
+        Self {
+            site_url: site_url.to_string(),
+            renderer,
+        }
+    }
+
+    pub fn renderer(&self) -> Option<&Arc<OgImageRenderer>> {
+        self.renderer.as_ref()
+    }
+
+    pub fn for_home(&self, cv_data: &CVData) -> SocialMeta {
+        self.without_missing_image(SocialMeta::for_home(&self.site_url, cv_data))
+    }
+
+    pub fn for_post(&self, post: &BlogPost, cv_data: &CVData) -> SocialMeta {
+        self.without_missing_image(SocialMeta::for_post(&self.site_url, post, cv_data))
+    }
+
+    fn without_missing_image(&self, mut meta: SocialMeta) -> SocialMeta {
+        if self.renderer.is_none() {
+            meta.image = None;
+        }
+        meta
+    }
+}
+
+pub fn post_card_path(slug: &str) -> String {
+    format!("/og/blog/{}.png", slug)
+}
+
+/// Cut `text` at a word boundary to at most `max_chars` characters
+fn summarize(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+    format!(
+        "{}…",
+        cut.trim_end_matches(|c: char| c.is_ascii_punctuation())
+    )
+}
+
+pub struct OgImageRenderer {
+    regular: FontVec,
+    bold: FontVec,
+    logo: Option<RgbaImage>,
+    site_label: String,
+    cache_dir: PathBuf,
+}
+
+impl OgImageRenderer {
+    /// Load Inter and the site logo from the static files; cards are cached
+    /// in `cache_dir`
+    pub fn new(
+        source: &AssetSource,
+        cache_dir: impl Into<PathBuf>,
+        site_url: &str,
+    ) -> Result<Self> {
+        let font = source
+            .read_static(FONT_PATH)
+            .with_context(|| format!("missing static file {}", FONT_PATH))?
+            .into_owned();
+
+        let mut regular = FontVec::try_from_vec(font.clone())?;
+        regular.set_variation(b"wght", 500.0);
+        let mut bold = FontVec::try_from_vec(font)?;
+        bold.set_variation(b"wght", 700.0);
+
+        let logo = source
+            .read_static(LOGO_PATH)
+            .with_context(|| format!("missing static file {}", LOGO_PATH))
+            .and_then(|logo| Ok(image::load_from_memory(&logo)?));
+        let logo = match logo {
+            Ok(logo) => Some(
+                logo.resize(LOGO_SIZE, LOGO_SIZE, FilterType::Lanczos3)
+                    .to_rgba8(),
+            ),
+            Err(err) => {
+                tracing::warn!("Open Graph cards without logo: {:#}", err);
+                None
+            }
+        };
+
+        let site_label = site_url
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .to_string();
+
+        Ok(Self {
+            regular,
+            bold,
+            logo,
+            site_label,
+            cache_dir: cache_dir.into(),
+        })
+    }
+
+    /// PNG bytes for `card`, rendered on first request and cached on disk
+    pub async fn png(self: &Arc<Self>, card: OgCard) -> Result<Vec<u8>> {
+        let path = self.cache_dir.join(format!("{}.png", card.hash()));
+        if let Ok(cached) = tokio::fs::read(&path).await {
+            return Ok(cached);
+        }
+
+        let renderer = self.clone();
+        let png = tokio::task::spawn_blocking(move || renderer.render(&card)).await??;
+
+        tokio::fs::create_dir_all(&self.cache_dir).await?;
+        let staging = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4().simple()));
+        tokio::fs::write(&staging, &png).await?;
+        tokio::fs::rename(&staging, &path).await?;
+        Ok(png)
+    }
+
+    fn render(&self, card: &OgCard) -> Result<Vec<u8>> {
+        let mut canvas = RgbaImage::from_fn(OG_WIDTH, OG_HEIGHT, |x, y| {
+            // Diagonal gradient from top-left to bottom-right
+            let t = (x as f32 / OG_WIDTH as f32 + y as f32 / OG_HEIGHT as f32) / 2.0;
+            let channel = |i: usize| {
+                (BACKGROUND_TOP[i] as f32
+                    + (BACKGROUND_BOTTOM[i] as f32 - BACKGROUND_TOP[i] as f32) * t)
+                    as u8
+            };
+            Rgba([channel(0), channel(1), channel(2), 255])
+        });
+        for y in 0..OG_HEIGHT {
+            for x in 0..12 {
+                canvas.put_pixel(x, y, Rgba([ACCENT[0], ACCENT[1], ACCENT[2], 255]));
+            }
+        }
+
+        // Header: logo and site name
+        let mut header_x = MARGIN;
+        if let Some(logo) = &self.logo {
+            imageops::overlay(&mut canvas, logo, MARGIN as i64, 64);
+            header_x += LOGO_SIZE as f32 + 24.0;
+        }
+        draw_text(
+            &mut canvas,
+            &self.regular,
+            34.0,
+            header_x,
+            64.0 + LOGO_SIZE as f32 / 2.0 + 12.0,
+            &self.site_label,
+            MUTED_COLOR,
+        );
+
+        // Title, shrinking to fit up to three lines
+        let max_width = OG_WIDTH as f32 - MARGIN * 2.0;
+        let (size, lines) = [72.0, 60.0, 52.0]
+            .iter()
+            .map(|&size| (size, wrap(&self.bold, size, &card.title, max_width, 3)))
+            .find(|(_, lines)| !lines.last().is_some_and(|line| line.ends_with('…')))
+            .unwrap_or_else(|| (52.0, wrap(&self.bold, 52.0, &card.title, max_width, 3)));
+        let mut baseline = 250.0 + size * 0.2;
+        for line in &lines {
+            draw_text(
+                &mut canvas,
+                &self.bold,
+                size,
+                MARGIN,
+                baseline,
+                line,
+                TEXT_COLOR,
+            );
+            baseline += size * 1.2;
+        }
+
+        // Tags in a row above the author, as many as fit
+        let mut tag_x = MARGIN;
+        for tag in &card.tags {
+            let label = format!("#{}", tag);
+            if tag_x + text_width(&self.regular, 30.0, &label) > MARGIN + max_width {
+                break;
+            }
+            tag_x += draw_text(
+                &mut canvas,
+                &self.regular,
+                30.0,
+                tag_x,
+                OG_HEIGHT as f32 - 120.0,
+                &label,
+                TAG_COLOR,
+            ) + 28.0;
+        }
+
+        draw_text(
+            &mut canvas,
+            &self.bold,
+            34.0,
+            MARGIN,
+            OG_HEIGHT as f32 - 64.0,
+            &card.author,
+            TEXT_COLOR,
+        );
+
+        let mut png = Vec::new();
+        canvas.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+        Ok(png)
+    }
+}
+
+/// Draw `text` with its baseline at `baseline`, returning the advance width
+fn draw_text(
+    canvas: &mut RgbaImage,
+    font: &FontVec,
+    size: f32,
+    x: f32,
+    baseline: f32,
+    text: &str,
+    color: [u8; 3],
+) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut caret = x;
+    let mut previous: Option<GlyphId> = None;
+
+    for ch in text.chars() {
+        let id = scaled.glyph_id(ch);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(size, point(caret, baseline));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= canvas.width() as i32 || py >= canvas.height() as i32 {
+                return;
+            }
+            let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+            let alpha = coverage.clamp(0.0, 1.0);
+            for (channel, target) in pixel.0.iter_mut().zip(color) {
+                *channel = (*channel as f32 * (1.0 - alpha) + target as f32 * alpha) as u8;
+            }
+        });
+    }
+    caret - x
+}
+
+fn text_width(font: &FontVec, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous: Option<GlyphId> = None;
+    for ch in text.chars() {
+        let id = scaled.glyph_id(ch);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// Greedy word wrap into at most `max_lines`, ending with `…` if truncated
+fn wrap(font: &FontVec, size: f32, text: &str, max_width: f32, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if text_width(font, size, &candidate) <= max_width || current.is_empty() {
+            current = candidate;
+            continue;
+        }
+
+        lines.push(std::mem::replace(&mut current, word.to_string()));
+        if lines.len() == max_lines {
+            let last = lines.pop().unwrap_or_default();
+            lines.push(ellipsize(font, size, &last, max_width));
+            return lines;
+        }
+    }
+    if !current.is_empty() {
+        if lines.len() == max_lines {
+            let last = lines.pop().unwrap_or_default();
+            lines.push(ellipsize(font, size, &last, max_width));
+        } else {
+            lines.push(current);
+        }
+    }
+    // A single word wider than the card is cut as well
+    if let Some(last) = lines.last_mut() {
+        if text_width(font, size, last) > max_width {
+            *last = ellipsize(font, size, last, max_width);
+        }
+    }
+    lines
+}
+
+fn ellipsize(font: &FontVec, size: f32, text: &str, max_width: f32) -> String {
+    let mut truncated: String = text.to_string();
+    while !truncated.is_empty() && text_width(font, size, &format!("{}…", truncated)) > max_width
+    {
+        truncated.pop();
+    }
+    format!("{}…", truncated.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card_renders_with_site_font_and_logo() {
+        let source = AssetSource::new(&crate::config::AppConfig::from_env());
+        let renderer =
+            OgImageRenderer::new(&source, std::env::temp_dir(), "https://example.com").unwrap();
+        assert!(renderer.logo.is_some());
+
+        let card = OgCard {
+            title: "A title long enough that it has to wrap across more than one line of the card"
+                .to_string(),
+            tags: vec!["rust".to_string(), "axum".to_string()],
+            author: "Author".to_string(),
+        };
+        let png = renderer.render(&card).unwrap();
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (OG_WIDTH, OG_HEIGHT));
+
+        let lines = wrap(&renderer.bold, 72.0, &card.title, 1040.0, 3);
+        assert!(lines.len() > 1 && lines.len() <= 3);
+    }
+
+    #[test]
+    fn test_missing_font_drops_card_images() {
+        let mut config = crate::config::AppConfig::from_env();
+        config.static_dir = std::env::temp_dir()
+            .join("og-missing-static")
+            .display()
+            .to_string();
+        let cards = SocialCards::new(
+            &AssetSource::new(&config),
+            std::env::temp_dir(),
+            "https://example.com",
+        );
+        assert!(cards.renderer().is_none());
+
+        let meta = cards.for_home(&CVData::default());
+        assert_eq!(meta.url, "https://example.com/");
+        assert!(meta.image.is_none());
+    }
+}
@@ -0,0 +1,345 @@
+// Rendered page cache
+//
+// Pages built only from the CV data, like the home page, come out the same
+// for every visitor until something they depend on changes. Rendered HTML is
+// cached with its Brotli and gzip variants, keyed by route, template version,
+// data version and negotiated locale:
+//
+// - the template version moves when templates are reloaded
+// - the data version moves on every blog change (posts appear on several
+//   pages, so any change drops every cached page)
+// - CV data is compiled into the binary, so it only changes with a restart,
+//   which starts from an empty cache
+//
+// Entries for superseded versions are dropped as soon as a newer version is
+// seen, and the cache is bounded by total bytes in LRU order.
+
+use crate::services::database::{BlogEvent, InMemoryBlogRepository};
+use crate::services::precompress::{self, Encoding, Level};
+use axum::body::{Body, Bytes};
+use axum::http::header;
+use axum::response::Response;
+use lru::LruCache;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PageKey {
+    pub route: String,
+    pub template_version: u64,
+    pub data_version: u64,
+    pub locale: String,
+}
+
+/// A rendered page and its precompressed variants
+pub struct RenderedPage {
+    html: Bytes,
+    brotli: Option<Bytes>,
+    gzip: Option<Bytes>,
+    /// Time the render took, saved again by every hit
+    render_time: Duration,
+}
+
+impl RenderedPage {
+    fn render(render: impl FnOnce() -> tera::Result<String>, compress: bool) -> tera::Result<Self> {
+        let started = Instant::now();
+        let html = Bytes::from(render()?);
+        let render_time = started.elapsed();
+
+        let variant = |encoding: Encoding| {
+            if !compress {
+                return None;
+            }
+            match precompress::compress(&html, encoding, Level::Balanced) {
+                Ok(compressed) => Some(Bytes::from(compressed)),
+                Err(e) => {
+                    tracing::warn!("Failed to precompress page: {}", e);
+                    None
+                }
+            }
+        };
+        Ok(Self {
+            brotli: variant(Encoding::Brotli),
+            gzip: variant(Encoding::Gzip),
+            html,
+            render_time,
+        })
+    }
+
+    /// HTML response in the first of `accepted` encodings with a variant
+    pub fn response(&self, accepted: &[Encoding]) -> Response {
+        let variant = accepted.iter().find_map(|&encoding| {
+            let bytes = match encoding {
+                Encoding::Brotli => self.brotli.as_ref(),
+                Encoding::Gzip => self.gzip.as_ref(),
+            };
+            bytes.map(|bytes| (bytes.clone(), encoding))
+        });
+
+        let mut builder = Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .header(header::VARY, "accept-encoding");
+        let body = match variant {
+            Some((bytes, encoding)) => {
+                // A Content-Encoding header also makes CompressionLayer pass the body through
+                builder = builder.header(header::CONTENT_ENCODING, encoding.token());
+                bytes
+            }
+            None => self.html.clone(),
+        };
+        builder.body(Body::from(body)).unwrap()
+    }
+
+    fn size(&self) -> u64 {
+        (self.html.len()
+            + self.brotli.as_ref().map_or(0, Bytes::len)
+            + self.gzip.as_ref().map_or(0, Bytes::len)) as u64
+    }
+}
+
+/// Point-in-time cache counters, as served on `/metrics`
+#[derive(Debug, Clone, Serialize)]
+pub struct PageCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+    /// Render time hits didn't have to spend
+    pub render_seconds_saved: f64,
+}
+
+impl PageCacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+struct Entries {
+    pages: LruCache<PageKey, Arc<RenderedPage>>,
+    bytes: u64,
+    /// Newest versions seen; entries for older ones are stale
+    template_version: u64,
+    data_version: u64,
+}
+
+pub struct PageCache {
+    entries: Mutex<Entries>,
+    max_bytes: u64,
+    /// Locales pages are rendered in, the default first
+    locales: Vec<String>,
+    data_version: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+    evictions: AtomicU64,
+    micros_saved: AtomicU64,
+}
+
+impl PageCache {
+    /// Cache holding up to `max_bytes` of pages, variants included, in each
+    /// of `locales`; a `max_bytes` of 0 disables caching
+    pub fn new(max_bytes: u64, locales: Vec<String>) -> Self {
+        Self {
+            entries: Mutex::new(Entries {
+                pages: LruCache::unbounded(),
+                bytes: 0,
+                template_version: 0,
+                data_version: 0,
+            }),
+            max_bytes,
+            locales,
+            data_version: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            micros_saved: AtomicU64::new(0),
+        }
+    }
+
+    pub fn locales(&self) -> &[String] {
+        &self.locales
+    }
+
+    /// Version of the content pages are rendered from
+    pub fn data_version(&self) -> u64 {
+        self.data_version.load(Ordering::Relaxed)
+    }
+
+    /// Content changed: every cached page is stale
+    pub fn invalidate_data(&self) {
+        let version = self.data_version.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut entries = self.entries.lock().unwrap();
+        let template_version = entries.template_version;
+        self.supersede(&mut entries, template_version, version);
+    }
+
+    /// The page cached under `key`, or the output of `render` after caching it
+    pub fn get_or_render(
+        &self,
+        key: PageKey,
+        render: impl FnOnce() -> tera::Result<String>,
+    ) -> tera::Result<Arc<RenderedPage>> {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(page) = entries.pages.get(&key) {
+                let page = page.clone();
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                self.micros_saved
+                    .fetch_add(page.render_time.as_micros() as u64, Ordering::Relaxed);
+                return Ok(page);
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let page = Arc::new(RenderedPage::render(render, self.max_bytes > 0)?);
+        self.insert(key, page.clone());
+        Ok(page)
+    }
+
+    pub fn stats(&self) -> PageCacheStats {
+        let entries = self.entries.lock().unwrap();
+        PageCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: entries.pages.len(),
+            bytes: entries.bytes,
+            max_bytes: self.max_bytes,
+            render_seconds_saved: self.micros_saved.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+        }
+    }
+
+    fn insert(&self, key: PageKey, page: Arc<RenderedPage>) {
+        let size = page.size();
+        if size > self.max_bytes {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let template_version = entries.template_version.max(key.template_version);
+        let data_version = entries.data_version.max(key.data_version);
+        self.supersede(&mut entries, template_version, data_version);
+        // Rendered from data or templates that have since changed
+        if key.template_version < template_version || key.data_version < data_version {
+            return;
+        }
+
+        if let Some(previous) = entries.pages.put(key, page) {
+            entries.bytes -= previous.size();
+        }
+        entries.bytes += size;
+        while entries.bytes > self.max_bytes {
+            let Some((_, evicted)) = entries.pages.pop_lru() else {
+                break;
+            };
+            entries.bytes -= evicted.size();
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Move to newer versions, dropping the entries they make stale
+    fn supersede(&self, entries: &mut Entries, template_version: u64, data_version: u64) {
+        if (template_version, data_version) == (entries.template_version, entries.data_version) {
+            return;
+        }
+        entries.template_version = template_version;
+        entries.data_version = data_version;
+
+        let stale: Vec<PageKey> = entries
+            .pages
+            .iter()
+            .filter(|(key, _)| {
+                key.template_version < template_version || key.data_version < data_version
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            if let Some(page) = entries.pages.pop(&key) {
+                entries.bytes -= page.size();
+                self.invalidations.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Move the data version on every blog change
+pub fn spawn_page_cache_invalidator(cache: Arc<PageCache>, repo: Arc<InMemoryBlogRepository>) {
+    let mut events = repo.subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(BlogEvent::Saved(_) | BlogEvent::Deleted(_)) => cache.invalidate_data(),
+                // Missed events were changes too
+                Err(RecvError::Lagged(_)) => cache.invalidate_data(),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(route: &str, template_version: u64, data_version: u64) -> PageKey {
+        PageKey {
+            route: route.to_string(),
+            template_version,
+            data_version,
+            locale: "en".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_pages_are_reused_until_a_version_moves() {
+        let cache = PageCache::new(1024 * 1024, vec!["en".to_string()]);
+        let html = "<p>Hello</p>".repeat(200);
+        let render = || Ok(html.clone());
+
+        let first = cache.get_or_render(key("/", 0, 0), render).unwrap();
+        let second = cache.get_or_render(key("/", 0, 0), render).unwrap();
+        let third = cache
+            .get_or_render(key("/projects/a", 0, 0), render)
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &third));
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+
+        // Precompressed variants are served to clients that accept them
+        let response = first.response(&[Encoding::Gzip]);
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        let response = first.response(&[]);
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+
+        // A blog change drops every page
+        cache.invalidate_data();
+        assert_eq!(cache.data_version(), 1);
+        assert_eq!(cache.stats().entries, 0);
+        assert_eq!(cache.stats().invalidations, 2);
+
+        // So does a template reload, once a page is rendered with it
+        cache.get_or_render(key("/", 0, 1), render).unwrap();
+        cache.get_or_render(key("/", 1, 1), render).unwrap();
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.invalidations), (1, 3));
+
+        // A render that raced a newer version isn't kept
+        cache.get_or_render(key("/old", 0, 1), render).unwrap();
+        assert_eq!(cache.stats().entries, 1);
+    }
+}
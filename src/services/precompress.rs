@@ -0,0 +1,226 @@
+// Precompressed static assets
+//
+// Static files can ship with `.br` and `.gz` sidecars (Vite emits them, or
+// `personal_website precompress` generates them). The static handler serves a
+// sidecar when the client accepts its encoding, so assets are compressed once
+// at build time instead of on every request.
+
+use anyhow::{Context as _, Result};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Files smaller than this rarely compress enough to be worth a sidecar
+const MIN_SIZE: u64 = 1024;
+
+/// Extensions worth compressing; images, fonts other than TTF/OTF, and
+/// archives are already compressed
+const COMPRESSIBLE: &[&str] = &[
+    "css",
+    "js",
+    "mjs",
+    "map",
+    "json",
+    "svg",
+    "xml",
+    "txt",
+    "html",
+    "wasm",
+    "webmanifest",
+    "ttf",
+    "otf",
+    "ico",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    /// Server preference when the client weighs encodings equally
+    pub const ALL: [Encoding; 2] = [Encoding::Brotli, Encoding::Gzip];
+
+    /// `Content-Encoding` token
+    pub fn token(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// Sidecar file extension
+    pub fn extension(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gz",
+        }
+    }
+}
+
+/// Encodings acceptable to the client per `Accept-Encoding`, most preferred
+/// first. Entries with `q=0` are excluded; `*` covers encodings not listed.
+pub fn negotiate(accept_encoding: &str) -> Vec<Encoding> {
+    let mut wildcard = None;
+    let mut weights: Vec<(Encoding, f32)> = Vec::new();
+
+    for entry in accept_encoding.split(',') {
+        let mut parts = entry.split(';');
+        let token = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        match token.as_str() {
+            "br" => weights.push((Encoding::Brotli, quality)),
+            "gzip" | "x-gzip" => weights.push((Encoding::Gzip, quality)),
+            "*" => wildcard = Some(quality),
+            _ => {}
+        }
+    }
+
+    if let Some(quality) = wildcard {
+        for encoding in Encoding::ALL {
+            if !weights.iter().any(|(listed, _)| *listed == encoding) {
+                weights.push((encoding, quality));
+            }
+        }
+    }
+
+    weights.retain(|(_, quality)| *quality > 0.0);
+    // Stable sort keeps server preference (brotli first) between equal weights
+    weights.sort_by_key(|(encoding, _)| Encoding::ALL.iter().position(|e| e == encoding));
+    weights.sort_by(|a, b| b.1.total_cmp(&a.1));
+    weights.into_iter().map(|(encoding, _)| encoding).collect()
+}
+
+/// Outcome of a sidecar generation run
+#[derive(Debug, Default)]
+pub struct SidecarReport {
+    pub written: usize,
+    pub skipped: usize,
+    pub bytes_saved: u64,
+}
+
+/// Write missing (or stale) `.br` and `.gz` sidecars for every compressible
+/// file under `static_dir`, at maximum compression. Sidecars that would not
+/// be smaller than the original are not written.
+pub fn generate_sidecars(static_dir: impl AsRef<Path>) -> Result<SidecarReport> {
+    let mut report = SidecarReport::default();
+
+    for entry in WalkDir::new(static_dir.as_ref()) {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type().is_file() || !is_compressible(path) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if metadata.len() < MIN_SIZE {
+            continue;
+        }
+
+        let mut original = None;
+        for encoding in Encoding::ALL {
+            let sidecar = sidecar_path(path, encoding);
+            let up_to_date = fs::metadata(&sidecar)
+                .and_then(|sidecar| Ok(sidecar.modified()? >= metadata.modified()?))
+                .unwrap_or(false);
+            if up_to_date {
+                report.skipped += 1;
+                continue;
+            }
+
+            if original.is_none() {
+                original =
+                    Some(fs::read(path).with_context(|| format!("reading {}", path.display()))?);
+            }
+            let original = original.as_deref().unwrap_or_default();
+            let compressed = compress(original, encoding, Level::Best)?;
+            if compressed.len() >= original.len() {
+                continue;
+            }
+
+            fs::write(&sidecar, &compressed)
+                .with_context(|| format!("writing {}", sidecar.display()))?;
+            report.written += 1;
+            report.bytes_saved += (original.len() - compressed.len()) as u64;
+        }
+    }
+
+    Ok(report)
+}
+
+/// `{path}.br` or `{path}.gz`
+pub fn sidecar_path(path: &Path, encoding: Encoding) -> std::path::PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".");
+    sidecar.push(encoding.extension());
+    sidecar.into()
+}
+
+/// Whether files like `path` are worth compressing
+pub fn is_compressible(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| COMPRESSIBLE.contains(&ext))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Smallest output, for build-time sidecars
+    Best,
+    /// Good ratio at a fraction of the CPU, for work done while serving
+    Balanced,
+}
+
+pub fn compress(data: &[u8], encoding: Encoding, level: Level) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    match encoding {
+        Encoding::Brotli => {
+            let (quality, lgwin) = match level {
+                Level::Best => (11, 24),
+                Level::Balanced => (6, 22),
+            };
+            let params = brotli::enc::BrotliEncoderParams {
+                quality,
+                lgwin,
+                ..Default::default()
+            };
+            brotli::BrotliCompress(&mut &data[..], &mut out, &params)?;
+        }
+        Encoding::Gzip => {
+            let level = match level {
+                Level::Best => flate2::Compression::best(),
+                Level::Balanced => flate2::Compression::default(),
+            };
+            let mut encoder = flate2::write::GzEncoder::new(&mut out, level);
+            encoder.write_all(data)?;
+            encoder.finish()?;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_orders_by_quality() {
+        assert_eq!(
+            negotiate("gzip, deflate, br"),
+            vec![Encoding::Brotli, Encoding::Gzip]
+        );
+        assert_eq!(
+            negotiate("br;q=0.5, gzip"),
+            vec![Encoding::Gzip, Encoding::Brotli]
+        );
+        assert_eq!(negotiate("gzip;q=1, br;q=0"), vec![Encoding::Gzip]);
+        assert_eq!(negotiate("*;q=0.1, gzip;q=0"), vec![Encoding::Brotli]);
+        assert!(negotiate("identity").is_empty());
+        assert!(negotiate("").is_empty());
+    }
+}
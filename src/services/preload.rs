@@ -0,0 +1,175 @@
+// `Link` preload headers for HTML pages
+//
+// Each route pattern lists its critical assets: Vite manifest entries (which
+// expand to their chunks and CSS) or plain static paths such as fonts. The
+// resulting `Link: rel=preload/modulepreload` header lets the browser start
+// fetching them before it has parsed the page, and lets a CDN that supports
+// it turn them into `103 Early Hints`. Static paths that don't exist at
+// startup are left out rather than preloading a 404.
+
+use crate::config::AppConfig;
+use crate::services::assets::{read_manifest, AssetInfo};
+use crate::services::embedded::AssetSource;
+use crate::services::vite::{entry_files, BASE};
+use axum::http::HeaderValue;
+use std::collections::HashMap;
+
+pub struct PreloadLinks {
+    /// `(route pattern, Link header value)`, first match wins
+    routes: Vec<(String, HeaderValue)>,
+}
+
+impl PreloadLinks {
+    pub fn new(config: &AppConfig, source: &AssetSource) -> Self {
+        // Modules on a dev server aren't worth preloading one by one
+        if config.vite_dev_server().is_some() {
+            return Self { routes: Vec::new() };
+        }
+        let manifest = read_manifest(source).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring unreadable Vite manifest for preload links: {}", e);
+            None
+        });
+        Self::with_manifest(manifest.as_ref(), &config.preload_routes, |path| {
+            source.has_static(path)
+        })
+    }
+
+    /// Links for `(route pattern, assets)`; a pattern is an exact path, a
+    /// prefix ending in `*`, or `*` for every page. Assets that aren't
+    /// manifest entries are linked only if `exists` finds the static file.
+    pub fn with_manifest(
+        manifest: Option<&HashMap<String, AssetInfo>>,
+        routes: &[(String, Vec<String>)],
+        exists: impl Fn(&str) -> bool,
+    ) -> Self {
+        let routes = routes
+            .iter()
+            .filter_map(|(pattern, assets)| {
+                let mut links: Vec<String> = Vec::new();
+                for asset in assets {
+                    for link in asset_links(manifest, asset, &exists) {
+                        if !links.contains(&link) {
+                            links.push(link);
+                        }
+                    }
+                }
+                let value = HeaderValue::from_str(&links.join(", ")).ok()?;
+                (!links.is_empty()).then(|| (pattern.clone(), value))
+            })
+            .collect();
+        Self { routes }
+    }
+
+    /// `Link` header value for a request path, if its route has any
+    pub fn for_path(&self, path: &str) -> Option<&HeaderValue> {
+        self.routes
+            .iter()
+            .find(|(pattern, _)| match pattern.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == pattern,
+            })
+            .map(|(_, value)| value)
+    }
+}
+
+/// Links for a manifest entry (its modules and CSS), or a single static file
+/// that exists
+fn asset_links(
+    manifest: Option<&HashMap<String, AssetInfo>>,
+    asset: &str,
+    exists: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let files = manifest.and_then(|manifest| entry_files(manifest, asset));
+    match files {
+        Some(files) => files
+            .stylesheets
+            .into_iter()
+            .chain(files.modules)
+            .filter_map(file_link)
+            .collect(),
+        None if exists(asset) => file_link(asset).into_iter().collect(),
+        None => Vec::new(),
+    }
+}
+
+/// `Link` for a static file, by type; `None` for types not worth preloading
+fn file_link(file: &str) -> Option<String> {
+    let extension = file.rsplit_once('.').map_or("", |(_, ext)| ext);
+    let kind = match extension {
+        "css" => "rel=preload; as=style",
+        "js" | "mjs" => "rel=modulepreload",
+        "woff2" => "rel=preload; as=font; type=\"font/woff2\"; crossorigin",
+        "png" | "jpg" | "jpeg" | "webp" | "avif" | "svg" => "rel=preload; as=image",
+        _ => return None,
+    };
+    Some(format!("<{}{}>; {}", BASE, file, kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_expand_manifest_entries() {
+        let manifest: HashMap<String, AssetInfo> = serde_json::from_str(
+            r#"{
+                "js/main.js": {
+                    "file": "js/main-AbC123xY.js",
+                    "isEntry": true,
+                    "imports": ["_core-Dq3kLm9x.js"],
+                    "css": ["css/main-Zz9Yy8Xx.css"]
+                },
+                "_core-Dq3kLm9x.js": { "file": "js/core-Dq3kLm9x.js" }
+            }"#,
+        )
+        .unwrap();
+        let routes = vec![
+            (
+                "/".to_string(),
+                vec!["js/main.js".to_string(), "fonts/inter.woff2".to_string()],
+            ),
+            ("/blog/*".to_string(), vec!["css/style.css".to_string()]),
+            ("/empty".to_string(), vec!["notes.txt".to_string()]),
+            ("/missing".to_string(), vec!["css/missing.css".to_string()]),
+        ];
+        let on_disk = ["fonts/inter.woff2", "css/style.css", "notes.txt"];
+        let links =
+            PreloadLinks::with_manifest(Some(&manifest), &routes, |path| on_disk.contains(&path));
+
+        assert_eq!(
+            links.for_path("/").unwrap(),
+            &[
+                "</static/css/main-Zz9Yy8Xx.css>; rel=preload; as=style",
+                "</static/js/core-Dq3kLm9x.js>; rel=modulepreload",
+                "</static/js/main-AbC123xY.js>; rel=modulepreload",
+                "</static/fonts/inter.woff2>; rel=preload; as=font; type=\"font/woff2\"; crossorigin",
+            ]
+            .join(", ")
+        );
+        assert_eq!(
+            links.for_path("/blog/hello").unwrap(),
+            "</static/css/style.css>; rel=preload; as=style"
+        );
+        assert!(links.for_path("/blog").is_none());
+        assert!(links.for_path("/empty").is_none());
+        assert!(links.for_path("/missing").is_none());
+    }
+
+    #[test]
+    fn test_missing_files_are_not_preloaded_without_a_manifest() {
+        let routes = vec![(
+            "*".to_string(),
+            vec![
+                "css/style.css".to_string(),
+                "js/main.js".to_string(),
+                "fonts/inter.woff2".to_string(),
+            ],
+        )];
+        let links = PreloadLinks::with_manifest(None, &routes, |path| path == "fonts/inter.woff2");
+
+        assert_eq!(
+            links.for_path("/").unwrap(),
+            "</static/fonts/inter.woff2>; rel=preload; as=font; type=\"font/woff2\"; crossorigin"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+// Signed, expiring preview links for unpublished posts
+//
+// A token is `base64url(post_id || expires_at) . base64url(hmac)`, so a draft
+// can be shared without publishing it and without storing anything server-side.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Default lifetime of a preview link
+pub const DEFAULT_PREVIEW_TTL: Duration = Duration::days(7);
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum PreviewError {
+    #[error("malformed preview token")]
+    Malformed,
+    #[error("invalid preview token signature")]
+    BadSignature,
+    #[error("preview token expired")]
+    Expired,
+}
+
+pub struct PreviewSigner {
+    secret: Vec<u8>,
+}
+
+impl PreviewSigner {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            secret: secret.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn sign(&self, post_id: Uuid, expires_at: DateTime<Utc>) -> String {
+        let mut payload = Vec::with_capacity(24);
+        payload.extend_from_slice(post_id.as_bytes());
+        payload.extend_from_slice(&expires_at.timestamp().to_be_bytes());
+
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(self.mac(&payload).finalize().into_bytes())
+        )
+    }
+
+    /// Check the signature and expiry, returning the post the token grants
+    pub fn verify(&self, token: &str, now: DateTime<Utc>) -> Result<Uuid, PreviewError> {
+        let (payload, signature) = token.split_once('.').ok_or(PreviewError::Malformed)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| PreviewError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| PreviewError::Malformed)?;
+
+        if payload.len() != 24 {
+            return Err(PreviewError::Malformed);
+        }
+
+        // Constant-time comparison via the MAC implementation
+        self.mac(&payload)
+            .verify_slice(&signature)
+            .map_err(|_| PreviewError::BadSignature)?;
+
+        let post_id = Uuid::from_slice(&payload[..16]).map_err(|_| PreviewError::Malformed)?;
+        let expires_at = i64::from_be_bytes(payload[16..].try_into().unwrap());
+        if now.timestamp() > expires_at {
+            return Err(PreviewError::Expired);
+        }
+
+        Ok(post_id)
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(payload);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_expiry() {
+        let signer = PreviewSigner::new("secret");
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        let token = signer.sign(id, now + Duration::hours(1));
+
+        assert_eq!(signer.verify(&token, now), Ok(id));
+        assert_eq!(
+            signer.verify(&token, now + Duration::hours(2)),
+            Err(PreviewError::Expired)
+        );
+    }
+
+    #[test]
+    fn test_rejects_tampering_and_foreign_keys() {
+        let signer = PreviewSigner::new("secret");
+        let now = Utc::now();
+        let token = signer.sign(Uuid::new_v4(), now + Duration::hours(1));
+
+        let other = PreviewSigner::new("other-secret");
+        assert_eq!(other.verify(&token, now), Err(PreviewError::BadSignature));
+
+        let forged = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode([0u8; 24]),
+            token.split_once('.').unwrap().1
+        );
+        assert_eq!(signer.verify(&forged, now), Err(PreviewError::BadSignature));
+        assert_eq!(signer.verify("garbage", now), Err(PreviewError::Malformed));
+    }
+}
@@ -0,0 +1,140 @@
+// HTTP byte ranges (RFC 9110 §14)
+//
+// Parses `Range: bytes=...` against a known representation length. Overlapping
+// and adjacent ranges are coalesced, and requests with too many ranges are
+// ignored (served in full) rather than answered part by part.
+
+/// Requests listing more ranges than this get the whole representation
+const MAX_RANGES: usize = 16;
+
+/// Inclusive byte range within a representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// `Content-Range` value, e.g. `bytes 0-99/1234`
+    pub fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// Malformed or unsupported header: serve the full representation
+    Ignore,
+    /// No range overlaps the representation: 416
+    Unsatisfiable,
+    /// Sorted, non-overlapping ranges to serve with 206
+    Satisfiable(Vec<ByteRange>),
+}
+
+pub fn parse(header: &str, len: u64) -> RangeRequest {
+    let Some(specs) = header.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Ignore;
+    };
+    let specs: Vec<&str> = specs
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .collect();
+    if specs.is_empty() || specs.len() > MAX_RANGES {
+        return RangeRequest::Ignore;
+    }
+
+    let mut ranges = Vec::with_capacity(specs.len());
+    for spec in specs {
+        let Some((first, last)) = spec.split_once('-') else {
+            return RangeRequest::Ignore;
+        };
+        let range = match (first.trim(), last.trim()) {
+            ("", suffix) => match suffix.parse::<u64>() {
+                Ok(0) => None,
+                Ok(suffix) if len > 0 => Some(ByteRange {
+                    start: len.saturating_sub(suffix),
+                    end: len - 1,
+                }),
+                Ok(_) => None,
+                Err(_) => return RangeRequest::Ignore,
+            },
+            (first, last) => {
+                let Ok(start) = first.parse::<u64>() else {
+                    return RangeRequest::Ignore;
+                };
+                let end = match last {
+                    "" => u64::MAX,
+                    last => match last.parse::<u64>() {
+                        Ok(end) if end >= start => end,
+                        _ => return RangeRequest::Ignore,
+                    },
+                };
+                (start < len).then(|| ByteRange {
+                    start,
+                    end: end.min(len - 1),
+                })
+            }
+        };
+        ranges.extend(range);
+    }
+
+    if ranges.is_empty() {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    RangeRequest::Satisfiable(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(pairs: &[(u64, u64)]) -> RangeRequest {
+        RangeRequest::Satisfiable(
+            pairs
+                .iter()
+                .map(|&(start, end)| ByteRange { start, end })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        assert_eq!(parse("bytes=0-99", 1000), ranges(&[(0, 99)]));
+        assert_eq!(parse("bytes=900-", 1000), ranges(&[(900, 999)]));
+        assert_eq!(parse("bytes=-100", 1000), ranges(&[(900, 999)]));
+        assert_eq!(parse("bytes=990-2000", 1000), ranges(&[(990, 999)]));
+        assert_eq!(parse("bytes=-5000", 1000), ranges(&[(0, 999)]));
+
+        // Coalesced and sorted
+        assert_eq!(
+            parse("bytes=500-599, 0-9, 10-19, 550-700", 1000),
+            ranges(&[(0, 19), (500, 700)])
+        );
+
+        assert_eq!(parse("bytes=1000-", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=0-", 0), RangeRequest::Unsatisfiable);
+
+        assert_eq!(parse("items=0-1", 1000), RangeRequest::Ignore);
+        assert_eq!(parse("bytes=5-1", 1000), RangeRequest::Ignore);
+        assert_eq!(parse("bytes=abc", 1000), RangeRequest::Ignore);
+        let many = format!("bytes={}", vec!["0-1"; MAX_RANGES + 1].join(","));
+        assert_eq!(parse(&many, 1000), RangeRequest::Ignore);
+    }
+}
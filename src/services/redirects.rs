@@ -0,0 +1,114 @@
+// Slug history and permanent redirects
+//
+// When a post or project is renamed its old slugs are remembered here, keyed
+// by the entity they belonged to, so inbound links can be answered with a 301
+// to the canonical URL instead of a 404.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RedirectScope {
+    Blog,
+    Project,
+}
+
+#[derive(Default)]
+pub struct RedirectTable {
+    /// (scope, old slug) -> entity id
+    entries: RwLock<HashMap<(RedirectScope, String), String>>,
+}
+
+impl RedirectTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember that `entity_id` moved from `old_slug` to `new_slug`
+    pub fn record_rename(
+        &self,
+        scope: RedirectScope,
+        entity_id: &str,
+        old_slug: &str,
+        new_slug: &str,
+    ) {
+        if old_slug == new_slug {
+            return;
+        }
+
+        let mut entries = self.entries.write().unwrap();
+        // A slug that is live again must not redirect anywhere
+        entries.remove(&(scope, new_slug.to_string()));
+        entries.insert((scope, old_slug.to_string()), entity_id.to_string());
+    }
+
+    /// Entity that used to live at `slug`, if any
+    pub fn lookup(&self, scope: RedirectScope, slug: &str) -> Option<String> {
+        self.entries
+            .read()
+            .unwrap()
+            .get(&(scope, slug.to_string()))
+            .cloned()
+    }
+
+    /// Every previous slug of an entity, sorted for stable output
+    pub fn history(&self, scope: RedirectScope, entity_id: &str) -> Vec<String> {
+        let mut slugs: Vec<String> = self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|((entry_scope, _), id)| *entry_scope == scope && id.as_str() == entity_id)
+            .map(|((_, slug), _)| slug.clone())
+            .collect();
+        slugs.sort();
+        slugs
+    }
+
+    /// Drop all history for a deleted entity
+    pub fn forget(&self, scope: RedirectScope, entity_id: &str) {
+        self.entries
+            .write()
+            .unwrap()
+            .retain(|(entry_scope, _), id| !(*entry_scope == scope && id.as_str() == entity_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_chain_resolves_to_entity() {
+        let table = RedirectTable::new();
+        table.record_rename(RedirectScope::Blog, "post-1", "first", "second");
+        table.record_rename(RedirectScope::Blog, "post-1", "second", "third");
+
+        assert_eq!(
+            table.lookup(RedirectScope::Blog, "first").as_deref(),
+            Some("post-1")
+        );
+        assert_eq!(
+            table.lookup(RedirectScope::Blog, "second").as_deref(),
+            Some("post-1")
+        );
+        assert_eq!(table.lookup(RedirectScope::Project, "first"), None);
+        assert_eq!(
+            table.history(RedirectScope::Blog, "post-1"),
+            vec!["first", "second"]
+        );
+    }
+
+    #[test]
+    fn test_renaming_back_reclaims_slug() {
+        let table = RedirectTable::new();
+        table.record_rename(RedirectScope::Blog, "post-1", "original", "renamed");
+        table.record_rename(RedirectScope::Blog, "post-1", "renamed", "original");
+
+        assert_eq!(table.lookup(RedirectScope::Blog, "original"), None);
+        assert_eq!(
+            table.lookup(RedirectScope::Blog, "renamed").as_deref(),
+            Some("post-1")
+        );
+    }
+}
@@ -0,0 +1,257 @@
+// Blog post revision history
+//
+// Every save of a post is kept as a numbered snapshot so edits can be
+// reviewed as line diffs and rolled back. A retention policy bounds how many
+// snapshots are kept per post.
+
+use crate::models::{BlogPost, BlogRevision};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Author recorded when a save does not name one
+pub const DEFAULT_REVISION_AUTHOR: &str = "admin";
+
+/// How many revisions to keep per post. The newest revision is always kept.
+#[derive(Debug, Clone, Copy)]
+pub struct RevisionRetention {
+    pub max_revisions: usize,
+    pub max_age: Option<Duration>,
+}
+
+impl Default for RevisionRetention {
+    fn default() -> Self {
+        Self {
+            max_revisions: 50,
+            max_age: None,
+        }
+    }
+}
+
+impl RevisionRetention {
+    fn prune(&self, revisions: &mut Vec<BlogRevision>, now: DateTime<Utc>) {
+        let max_revisions = self.max_revisions.max(1);
+        if revisions.len() > max_revisions {
+            revisions.drain(..revisions.len() - max_revisions);
+        }
+
+        if let Some(max_age) = self.max_age {
+            let cutoff = now - max_age;
+            let newest = revisions.last().map(|revision| revision.number);
+            revisions.retain(|revision| {
+                revision.created_at >= cutoff || Some(revision.number) == newest
+            });
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RevisionStore {
+    revisions: RwLock<HashMap<Uuid, Vec<BlogRevision>>>,
+    retention: RevisionRetention,
+}
+
+impl RevisionStore {
+    pub fn new(retention: RevisionRetention) -> Self {
+        Self {
+            revisions: RwLock::new(HashMap::new()),
+            retention,
+        }
+    }
+
+    /// Snapshot `post` as its next revision and apply the retention policy
+    pub fn record(
+        &self,
+        post: &BlogPost,
+        author: Option<String>,
+        summary: Option<String>,
+    ) -> BlogRevision {
+        let mut revisions = self.revisions.write().unwrap();
+        let history = revisions.entry(post.id).or_default();
+        let revision = BlogRevision {
+            post_id: post.id,
+            number: history.last().map_or(1, |last| last.number + 1),
+            title: post.title.clone(),
+            content: post.content.clone(),
+            excerpt: post.excerpt.clone(),
+            tags: post.tags.clone(),
+            author: author
+                .filter(|author| !author.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_REVISION_AUTHOR.to_string()),
+            summary: summary.filter(|summary| !summary.trim().is_empty()),
+            created_at: post.updated_at,
+        };
+
+        history.push(revision.clone());
+        self.retention.prune(history, Utc::now());
+        revision
+    }
+
+    /// Revisions of a post, newest first
+    pub fn list(&self, post_id: Uuid) -> Vec<BlogRevision> {
+        let mut revisions = self
+            .revisions
+            .read()
+            .unwrap()
+            .get(&post_id)
+            .cloned()
+            .unwrap_or_default();
+        revisions.reverse();
+        revisions
+    }
+
+    pub fn get(&self, post_id: Uuid, number: u32) -> Option<BlogRevision> {
+        self.revisions
+            .read()
+            .unwrap()
+            .get(&post_id)?
+            .iter()
+            .find(|revision| revision.number == number)
+            .cloned()
+    }
+
+    pub fn forget(&self, post_id: Uuid) {
+        self.revisions.write().unwrap().remove(&post_id);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    /// 1-based line number in the older revision
+    pub old_line: Option<usize>,
+    /// 1-based line number in the newer revision
+    pub new_line: Option<usize>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionDiff {
+    pub from: u32,
+    pub to: u32,
+    pub title_changed: bool,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Line-level diff of the content of two revisions
+pub fn diff_revisions(from: &BlogRevision, to: &BlogRevision) -> RevisionDiff {
+    let diff = TextDiff::from_lines(&from.content, &to.content);
+    let lines: Vec<DiffLine> = diff
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            op: match change.tag() {
+                ChangeTag::Equal => DiffOp::Equal,
+                ChangeTag::Insert => DiffOp::Insert,
+                ChangeTag::Delete => DiffOp::Delete,
+            },
+            old_line: change.old_index().map(|index| index + 1),
+            new_line: change.new_index().map(|index| index + 1),
+            text: change.value().trim_end_matches(['\r', '\n']).to_string(),
+        })
+        .collect();
+
+    RevisionDiff {
+        from: from.number,
+        to: to.number,
+        title_changed: from.title != to.title,
+        insertions: lines
+            .iter()
+            .filter(|line| line.op == DiffOp::Insert)
+            .count(),
+        deletions: lines
+            .iter()
+            .filter(|line| line.op == DiffOp::Delete)
+            .count(),
+        lines,
+    }
+}
+
+/// Diff between two revisions from a newest-first list. `to` defaults to the
+/// newest revision and `from` to the one saved just before `to`.
+pub fn diff_between(
+    revisions: &[BlogRevision],
+    from: Option<u32>,
+    to: Option<u32>,
+) -> Option<RevisionDiff> {
+    let find = |number: u32| revisions.iter().find(|revision| revision.number == number);
+    let to = match to {
+        Some(number) => find(number)?,
+        None => revisions.first()?,
+    };
+    let from = match from {
+        Some(number) => find(number)?,
+        None => revisions
+            .iter()
+            .find(|revision| revision.number < to.number)
+            .unwrap_or(to),
+    };
+    Some(diff_revisions(from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(content: &str) -> BlogPost {
+        let now = Utc::now();
+        BlogPost {
+            id: Uuid::nil(),
+            title: "Post".to_string(),
+            slug: "post".to_string(),
+            content: content.to_string(),
+            excerpt: None,
+            published: true,
+            publish_at: None,
+            created_at: now,
+            updated_at: now,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_retention_keeps_newest_revisions() {
+        let store = RevisionStore::new(RevisionRetention {
+            max_revisions: 2,
+            max_age: None,
+        });
+        for content in ["one", "two", "three"] {
+            store.record(&post(content), None, None);
+        }
+
+        let numbers: Vec<u32> = store.list(Uuid::nil()).iter().map(|r| r.number).collect();
+        assert_eq!(numbers, vec![3, 2]);
+        assert!(store.get(Uuid::nil(), 1).is_none());
+        assert_eq!(
+            store.get(Uuid::nil(), 3).unwrap().author,
+            DEFAULT_REVISION_AUTHOR
+        );
+    }
+
+    #[test]
+    fn test_line_diff_counts_changes() {
+        let store = RevisionStore::default();
+        let old = store.record(&post("a\nb\nc\n"), None, None);
+        let new = store.record(&post("a\nB\nc\nd\n"), None, Some("Edit".into()));
+
+        let diff = diff_revisions(&old, &new);
+        assert_eq!((diff.insertions, diff.deletions), (2, 1));
+        assert_eq!(diff.lines[0].op, DiffOp::Equal);
+        assert!(diff
+            .lines
+            .iter()
+            .any(|line| line.op == DiffOp::Delete && line.text == "b" && line.old_line == Some(2)));
+    }
+}
@@ -0,0 +1,271 @@
+// Safe resolution of request paths under the static root
+//
+// Request paths are checked segment by segment before they touch the
+// filesystem: parent and current-directory segments, absolute paths, empty
+// segments, backslashes and anything still percent-encoded after decoding are
+// rejected outright. What is left is canonicalized and must still lie under
+// the canonical root, so symlinks cannot point outside it. Hidden files and
+// directories are refused unless allowlisted (e.g. `.well-known`).
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum PathError {
+    #[error("path contains a forbidden segment")]
+    Forbidden,
+    #[error("hidden files are not served")]
+    Hidden,
+    #[error("path resolves outside the static root")]
+    OutsideRoot,
+    #[error("file not found")]
+    NotFound,
+}
+
+pub struct StaticPathResolver {
+    /// Canonical root; `None` when the directory does not exist
+    root: Option<PathBuf>,
+    hidden_allowlist: Vec<String>,
+}
+
+impl StaticPathResolver {
+    /// `hidden_allowlist` holds root-relative paths (e.g. `.well-known`)
+    /// under which hidden segments are allowed
+    pub fn new(root: impl AsRef<Path>, hidden_allowlist: Vec<String>) -> Self {
+        Self {
+            root: std::fs::canonicalize(root).ok(),
+            hidden_allowlist: hidden_allowlist
+                .into_iter()
+                .map(|entry| entry.trim_matches('/').to_string())
+                .filter(|entry| !entry.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Lexically validate a decoded request path, returning it as a clean
+    /// root-relative path. Does not touch the filesystem.
+    pub fn sanitize(&self, path: &str) -> Result<String, PathError> {
+        if path.is_empty() || path.starts_with('/') {
+            return Err(PathError::Forbidden);
+        }
+
+        for segment in path.split('/') {
+            // A `%` left after decoding means the path was encoded twice,
+            // typically to smuggle `%2e%2e` or `%2f` past a single decode
+            let forbidden = segment.is_empty()
+                || segment == "."
+                || segment == ".."
+                || segment.contains(['\\', '\0', ':', '%']);
+            if forbidden {
+                return Err(PathError::Forbidden);
+            }
+        }
+
+        if !self.hidden_allowed(path) {
+            return Err(PathError::Hidden);
+        }
+        Ok(path.to_string())
+    }
+
+    /// Sanitize `path` and resolve it to an existing file under the root,
+    /// following symlinks only as long as they stay inside it
+    pub async fn resolve(&self, path: &str) -> Result<PathBuf, PathError> {
+        let relative = self.sanitize(path)?;
+        let root = self.root.as_ref().ok_or(PathError::NotFound)?;
+
+        let resolved = tokio::fs::canonicalize(root.join(&relative))
+            .await
+            .map_err(|_| PathError::NotFound)?;
+        let Ok(inside) = resolved.strip_prefix(root) else {
+            return Err(PathError::OutsideRoot);
+        };
+
+        // A symlink inside the root may still lead to a hidden file
+        let target = inside
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if !self.hidden_allowed(&target) {
+            return Err(PathError::Hidden);
+        }
+        Ok(resolved)
+    }
+
+    /// Paths with a hidden segment are only served when they are, or lie
+    /// under, an allowlisted path
+    fn hidden_allowed(&self, path: &str) -> bool {
+        if !path.split('/').any(|segment| segment.starts_with('.')) {
+            return true;
+        }
+        self.hidden_allowlist
+            .iter()
+            .any(|allowed| path == allowed || path.starts_with(&format!("{}/", allowed)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixture {
+        dir: PathBuf,
+        resolver: StaticPathResolver,
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// `{tmp}/root` with regular, hidden and allowlisted files, next to a
+    /// `secret.txt` outside the root
+    fn fixture() -> Fixture {
+        let dir = std::env::temp_dir().join(format!("safe-path-{}", uuid::Uuid::new_v4()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("css")).unwrap();
+        std::fs::create_dir_all(root.join(".vite")).unwrap();
+        std::fs::create_dir_all(root.join(".well-known")).unwrap();
+        std::fs::write(root.join("css/style.css"), "body {}").unwrap();
+        std::fs::write(root.join(".vite/manifest.json"), "{}").unwrap();
+        std::fs::write(root.join(".env"), "SECRET=1").unwrap();
+        std::fs::write(root.join(".well-known/security.txt"), "Contact: x").unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+
+        let resolver = StaticPathResolver::new(&root, vec![".well-known".to_string()]);
+        Fixture { dir, resolver }
+    }
+
+    #[test]
+    fn test_rejects_traversal_payloads() {
+        let fixture = fixture();
+        let payloads = [
+            "",
+            "../secret.txt",
+            "css/../../secret.txt",
+            "css/../style.css",
+            "./css/style.css",
+            "css/./style.css",
+            "..",
+            "/etc/passwd",
+            "//etc/passwd",
+            "css//style.css",
+            "css/",
+            "..\\secret.txt",
+            "css\\..\\..\\secret.txt",
+            "C:\\Windows\\win.ini",
+            "C:/Windows/win.ini",
+            "%2e%2e/secret.txt",
+            "%2E%2E/secret.txt",
+            "..%2fsecret.txt",
+            "..%2Fsecret.txt",
+            "..%5csecret.txt",
+            "css%2f..%2f..%2fsecret.txt",
+            "%252e%252e/secret.txt",
+            "css/style.css%00.png",
+            "css/style.css\0.png",
+            "....//....//secret.txt",
+        ];
+        for payload in payloads {
+            let result = fixture.resolver.sanitize(payload);
+            assert!(
+                matches!(result, Err(PathError::Forbidden)),
+                "{:?} was not rejected: {:?}",
+                payload,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_hidden_files_need_allowlist() {
+        let fixture = fixture();
+        let resolver = &fixture.resolver;
+        for hidden in [
+            ".env",
+            ".vite/manifest.json",
+            "css/.htaccess",
+            ".git/config",
+        ] {
+            assert_eq!(
+                resolver.sanitize(hidden),
+                Err(PathError::Hidden),
+                "{}",
+                hidden
+            );
+        }
+        assert_eq!(
+            resolver.sanitize(".well-known/security.txt").as_deref(),
+            Ok(".well-known/security.txt")
+        );
+        // The allowlist covers the named path and what lies under it only
+        assert_eq!(resolver.sanitize(".well-knownx/a"), Err(PathError::Hidden));
+        assert_eq!(
+            resolver.sanitize("css/style.css").as_deref(),
+            Ok("css/style.css")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolves_inside_root_only() {
+        let fixture = fixture();
+        let resolver = &fixture.resolver;
+        let resolved = resolver.resolve("css/style.css").await.unwrap();
+        assert!(resolved.ends_with("root/css/style.css"));
+        assert_eq!(
+            resolver.resolve("css/missing.css").await,
+            Err(PathError::NotFound)
+        );
+        assert_eq!(
+            resolver.resolve("../secret.txt").await,
+            Err(PathError::Forbidden)
+        );
+
+        let missing_root = StaticPathResolver::new(fixture.dir.join("nope"), Vec::new());
+        assert_eq!(
+            missing_root.resolve("css/style.css").await,
+            Err(PathError::NotFound)
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlinks_cannot_escape_root() {
+        use std::os::unix::fs::symlink;
+
+        let fixture = fixture();
+        let root = fixture.dir.join("root");
+        symlink(fixture.dir.join("secret.txt"), root.join("escape.txt")).unwrap();
+        symlink(&fixture.dir, root.join("parent")).unwrap();
+        symlink("/etc", root.join("etc")).unwrap();
+        symlink(root.join(".env"), root.join("env.txt")).unwrap();
+        symlink(root.join("css/style.css"), root.join("alias.css")).unwrap();
+
+        let resolver = &fixture.resolver;
+        for escaping in [
+            "escape.txt",
+            "parent/secret.txt",
+            "etc/passwd",
+            "etc/hostname",
+        ] {
+            let result = resolver.resolve(escaping).await;
+            assert!(
+                matches!(result, Err(PathError::OutsideRoot | PathError::NotFound)),
+                "{:?} escaped the root: {:?}",
+                escaping,
+                result
+            );
+        }
+        assert_eq!(
+            resolver.resolve("escape.txt").await,
+            Err(PathError::OutsideRoot)
+        );
+        // Links within the root work, unless they lead to a hidden file
+        assert_eq!(resolver.resolve("env.txt").await, Err(PathError::Hidden));
+        assert!(resolver
+            .resolve("alias.css")
+            .await
+            .unwrap()
+            .ends_with("root/css/style.css"));
+    }
+}
@@ -0,0 +1,484 @@
+// Full-text search over blog posts, projects and CV experience
+//
+// A small in-memory inverted index. Documents are tokenized per field with a
+// field weight; queries match exact terms, prefixes and near-miss spellings
+// (edit distance), and results are ranked with a TF-IDF style score.
+
+use crate::models::{BlogPost, CVData};
+use crate::services::database::{BlogEvent, BlogRepository, InMemoryBlogRepository};
+use crate::services::markdown::plain_text;
+use crate::utils::slugify;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast::error::RecvError;
+
+/// Number of characters shown around the first match in a snippet
+const SNIPPET_LENGTH: usize = 160;
+
+/// Score multipliers for how a query term matched an index term
+const EXACT_MATCH: f64 = 1.0;
+const PREFIX_MATCH: f64 = 0.6;
+const FUZZY_MATCH: f64 = 0.4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentKind {
+    Post,
+    Project,
+    Experience,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchDocument {
+    pub id: String,
+    pub kind: DocumentKind,
+    pub title: String,
+    pub url: String,
+    /// Plain text used for snippets
+    pub body: String,
+    /// Weighted fields that feed the index
+    pub fields: Vec<(String, f64)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    pub id: String,
+    pub kind: DocumentKind,
+    pub title: String,
+    pub url: String,
+    /// HTML-escaped excerpt with matches wrapped in `<mark>`
+    pub snippet: String,
+    pub score: f64,
+}
+
+#[derive(Debug, Default)]
+struct Posting {
+    /// Sum of field weights for every occurrence of the term in the document
+    weight: f64,
+}
+
+#[derive(Default)]
+struct IndexInner {
+    documents: HashMap<String, SearchDocument>,
+    /// term -> document id -> posting; a BTreeMap so prefix lookups are range scans
+    terms: BTreeMap<String, HashMap<String, Posting>>,
+}
+
+#[derive(Default)]
+pub struct SearchIndex {
+    inner: RwLock<IndexInner>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index everything searchable from the CV and the published posts
+    pub fn build(cv_data: &CVData, posts: &[BlogPost]) -> Self {
+        let index = Self::new();
+        for document in cv_documents(cv_data) {
+            index.upsert(document);
+        }
+        let now = Utc::now();
+        for post in posts.iter().filter(|post| post.is_live(now)) {
+            index.upsert(post_document(post));
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add or replace a document, touching only the terms it contains
+    pub fn upsert(&self, document: SearchDocument) {
+        let mut inner = self.inner.write().unwrap();
+        inner.remove(&document.id);
+
+        for (text, weight) in &document.fields {
+            for token in tokenize(text) {
+                inner
+                    .terms
+                    .entry(token)
+                    .or_default()
+                    .entry(document.id.clone())
+                    .or_default()
+                    .weight += weight;
+            }
+        }
+        inner.documents.insert(document.id.clone(), document);
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.inner.write().unwrap().remove(id);
+    }
+
+    /// Make post documents match `posts`: live ones are reindexed and every
+    /// other `post:*` document, including those of deleted posts, removed
+    pub fn sync_posts(&self, posts: &[BlogPost], now: DateTime<Utc>) {
+        let mut live = HashSet::new();
+        for post in posts.iter().filter(|post| post.is_live(now)) {
+            self.upsert(post_document(post));
+            live.insert(post_document_id(&post.id));
+        }
+
+        let stale: Vec<String> = self
+            .inner
+            .read()
+            .unwrap()
+            .documents
+            .keys()
+            .filter(|id| id.starts_with(POST_ID_PREFIX) && !live.contains(*id))
+            .cloned()
+            .collect();
+        for id in stale {
+            self.remove(&id);
+        }
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchResult> {
+        let query_terms: Vec<String> = tokenize(query).collect();
+        if query_terms.is_empty() {
+            return Vec::new();
+        }
+
+        let inner = self.inner.read().unwrap();
+        let total_docs = inner.documents.len().max(1) as f64;
+        let mut scores: HashMap<&str, f64> = HashMap::new();
+        let mut matched_terms: HashSet<&str> = HashSet::new();
+
+        for query_term in &query_terms {
+            // Best match quality per document for this query term
+            let mut best: HashMap<&str, f64> = HashMap::new();
+
+            for (term, quality) in inner.matching_terms(query_term) {
+                let postings = &inner.terms[term];
+                let idf = (1.0 + total_docs / postings.len() as f64).ln();
+                matched_terms.insert(term);
+
+                for (doc_id, posting) in postings {
+                    let score = quality * idf * (1.0 + posting.weight.ln_1p());
+                    let entry = best.entry(doc_id.as_str()).or_insert(0.0);
+                    if score > *entry {
+                        *entry = score;
+                    }
+                }
+            }
+
+            for (doc_id, score) in best {
+                *scores.entry(doc_id).or_insert(0.0) += score;
+            }
+        }
+
+        let mut results: Vec<SearchResult> = scores
+            .into_iter()
+            .filter_map(|(doc_id, score)| {
+                let document = inner.documents.get(doc_id)?;
+                Some(SearchResult {
+                    id: document.id.clone(),
+                    kind: document.kind,
+                    title: document.title.clone(),
+                    url: document.url.clone(),
+                    snippet: snippet(&document.body, &matched_terms),
+                    score,
+                })
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.title.cmp(&b.title))
+        });
+        results.truncate(limit);
+        results
+    }
+}
+
+impl IndexInner {
+    fn remove(&mut self, id: &str) {
+        let Some(document) = self.documents.remove(id) else {
+            return;
+        };
+
+        for (text, _) in &document.fields {
+            for token in tokenize(text) {
+                if let Some(postings) = self.terms.get_mut(&token) {
+                    postings.remove(id);
+                    if postings.is_empty() {
+                        self.terms.remove(&token);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Index terms matching a query term, with a match-quality multiplier
+    fn matching_terms<'a>(&'a self, query_term: &str) -> Vec<(&'a str, f64)> {
+        let mut matches = Vec::new();
+
+        if let Some((term, _)) = self.terms.get_key_value(query_term) {
+            matches.push((term.as_str(), EXACT_MATCH));
+        }
+
+        // Prefix matching for partially typed words
+        if query_term.chars().count() >= 2 {
+            matches.extend(
+                self.terms
+                    .range(query_term.to_string()..)
+                    .take_while(|(term, _)| term.starts_with(query_term))
+                    .filter(|(term, _)| term.as_str() != query_term)
+                    .map(|(term, _)| (term.as_str(), PREFIX_MATCH)),
+            );
+        }
+
+        // Typo tolerance scales with word length
+        let max_distance = match query_term.chars().count() {
+            0..=3 => 0,
+            4..=7 => 1,
+            _ => 2,
+        };
+        if max_distance > 0 {
+            let already: HashSet<&str> = matches.iter().map(|(term, _)| *term).collect();
+            matches.extend(
+                self.terms
+                    .keys()
+                    .filter(|term| !already.contains(term.as_str()))
+                    .filter(|term| term.len().abs_diff(query_term.len()) <= max_distance)
+                    .filter(|term| strsim::levenshtein(term, query_term) <= max_distance)
+                    .map(|term| (term.as_str(), FUZZY_MATCH)),
+            );
+        }
+
+        matches
+    }
+}
+
+/// Keep the index in sync with blog changes; falls back to a full rebuild of
+/// post documents if notifications were dropped
+pub fn spawn_index_updater(index: Arc<SearchIndex>, repo: Arc<InMemoryBlogRepository>) {
+    let mut events = repo.subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(BlogEvent::Saved(post)) => {
+                    if post.is_live(Utc::now()) {
+                        index.upsert(post_document(&post));
+                    } else {
+                        index.remove(&post_document_id(&post.id));
+                    }
+                }
+                Ok(BlogEvent::Deleted(id)) => index.remove(&post_document_id(&id)),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Search index missed {} updates, reindexing posts", skipped);
+                    match repo.get_all_posts().await {
+                        Ok(posts) => index.sync_posts(&posts, Utc::now()),
+                        Err(err) => tracing::error!("Search reindex failed: {}", err),
+                    }
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+const POST_ID_PREFIX: &str = "post:";
+
+fn post_document_id(id: &uuid::Uuid) -> String {
+    format!("{}{}", POST_ID_PREFIX, id)
+}
+
+pub fn post_document(post: &BlogPost) -> SearchDocument {
+    let body = plain_text(&post.content);
+    SearchDocument {
+        id: post_document_id(&post.id),
+        kind: DocumentKind::Post,
+        title: post.title.clone(),
+        url: format!("/blog/{}", post.slug),
+        fields: vec![
+            (post.title.clone(), 3.0),
+            (post.tags.join(" "), 2.0),
+            (body.clone(), 1.0),
+        ],
+        body: post.excerpt.clone().unwrap_or(body),
+    }
+}
+
+pub fn cv_documents(cv_data: &CVData) -> Vec<SearchDocument> {
+    let projects = cv_data.projects.iter().map(|project| SearchDocument {
+        id: format!("project:{}", slugify(&project.name)),
+        kind: DocumentKind::Project,
+        title: project.name.clone(),
+        url: format!("/projects/{}", slugify(&project.name)),
+        body: project.description.clone(),
+        fields: vec![
+            (project.name.clone(), 3.0),
+            (project.technologies.join(" "), 2.0),
+            (project.description.clone(), 1.0),
+        ],
+    });
+
+    let experience = cv_data.experience.iter().enumerate().map(|(index, exp)| {
+        let bullets = exp.description.join(" ");
+        SearchDocument {
+            id: format!("experience:{}", index),
+            kind: DocumentKind::Experience,
+            title: format!("{} at {}", exp.title, exp.company),
+            url: "/#experience".to_string(),
+            body: bullets.clone(),
+            fields: vec![
+                (exp.title.clone(), 2.0),
+                (exp.company.clone(), 2.0),
+                (bullets, 1.0),
+            ],
+        }
+    });
+
+    projects.chain(experience).collect()
+}
+
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 2)
+        .map(|word| word.to_lowercase())
+}
+
+/// Excerpt around the first matched word, with matches highlighted
+fn snippet(body: &str, matched_terms: &HashSet<&str>) -> String {
+    let words: Vec<&str> = body.split_whitespace().collect();
+    let is_match = |word: &str| tokenize(word).any(|token| matched_terms.contains(token.as_str()));
+
+    let first = words.iter().position(|word| is_match(word)).unwrap_or(0);
+    // Start a few words before the match for context
+    let start = first.saturating_sub(5);
+
+    let mut out = String::new();
+    let mut length = 0;
+    if start > 0 {
+        out.push_str("… ");
+    }
+
+    for word in &words[start..] {
+        if length >= SNIPPET_LENGTH {
+            out.push('…');
+            break;
+        }
+        let escaped = html_escape(word);
+        if is_match(word) {
+            out.push_str(&format!("<mark>{}</mark> ", escaped));
+        } else {
+            out.push_str(&escaped);
+            out.push(' ');
+        }
+        length += word.len() + 1;
+    }
+
+    out.trim_end().to_string()
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> SearchIndex {
+        SearchIndex::build(&CVData::default(), &[])
+    }
+
+    #[test]
+    fn test_exact_and_prefix_matches() {
+        let index = index();
+
+        let exact = index.search("laravel", 10);
+        assert!(exact.iter().any(|r| r.title == "Hotel Management Platform"));
+
+        let prefix = index.search("forec", 10);
+        assert_eq!(prefix[0].title, "Stock Price Forecasting ML Tool");
+    }
+
+    #[test]
+    fn test_typo_tolerance() {
+        let results = index().search("tensorflw", 10);
+        assert_eq!(results[0].title, "Stock Price Forecasting ML Tool");
+    }
+
+    #[test]
+    fn test_snippet_highlights_matches() {
+        let results = index().search("hotel", 10);
+        assert!(results[0].snippet.contains("<mark>hotel</mark>"));
+    }
+
+    #[test]
+    fn test_incremental_upsert_and_remove() {
+        let index = SearchIndex::new();
+        let mut document = SearchDocument {
+            id: "post:1".to_string(),
+            kind: DocumentKind::Post,
+            title: "Axum tips".to_string(),
+            url: "/blog/axum-tips".to_string(),
+            body: "Routing with axum".to_string(),
+            fields: vec![("Axum tips".to_string(), 3.0)],
+        };
+        index.upsert(document.clone());
+        assert_eq!(index.search("axum", 10).len(), 1);
+
+        document.fields = vec![("Tokio tips".to_string(), 3.0)];
+        index.upsert(document);
+        assert!(index.search("axum", 10).is_empty());
+
+        index.remove("post:1");
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_sync_posts_drops_missed_deletions() {
+        let now = Utc::now();
+        let post = |title: &str| BlogPost {
+            id: uuid::Uuid::new_v4(),
+            title: title.to_string(),
+            slug: crate::utils::slugify(title),
+            content: format!("All about {}", title),
+            excerpt: None,
+            published: true,
+            publish_at: None,
+            created_at: now,
+            updated_at: now,
+            tags: Vec::new(),
+        };
+        let kept = post("Quokka");
+        let deleted = post("Wombat");
+        let unpublished = post("Zanzibar");
+        let index = SearchIndex::build(
+            &CVData::default(),
+            &[kept.clone(), deleted, unpublished.clone()],
+        );
+        let cv_documents = index.len() - 3;
+
+        // The deletion was never seen; the other post was unpublished since
+        let unpublished = BlogPost {
+            published: false,
+            ..unpublished
+        };
+        index.sync_posts(&[kept, unpublished], now);
+
+        assert_eq!(index.len(), cv_documents + 1);
+        assert_eq!(index.search("quokka", 10).len(), 1);
+        assert!(index.search("wombat", 10).is_empty());
+        assert!(index.search("zanzibar", 10).is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+// Signed, expiring admin sessions
+//
+// The admin area's login cookie is `base64url(expires_at) . base64url(hmac)`,
+// keyed by the admin token, so the cookie never carries the token itself,
+// stops working once it expires, and every session ends when the token is
+// rotated. Nothing is stored server-side.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Lifetime of an admin session
+pub const SESSION_TTL: Duration = Duration::hours(8);
+
+/// Keeps session MACs from being valid as any other MAC under the same key
+const SESSION_CONTEXT: &[u8] = b"admin-session";
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum SessionError {
+    #[error("malformed session")]
+    Malformed,
+    #[error("invalid session signature")]
+    BadSignature,
+    #[error("session expired")]
+    Expired,
+}
+
+pub struct SessionSigner {
+    secret: Vec<u8>,
+}
+
+impl SessionSigner {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            secret: secret.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn sign(&self, expires_at: DateTime<Utc>) -> String {
+        let payload = expires_at.timestamp().to_be_bytes();
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(self.mac(&payload).finalize().into_bytes())
+        )
+    }
+
+    /// Check the signature and expiry of a session cookie value
+    pub fn verify(&self, session: &str, now: DateTime<Utc>) -> Result<(), SessionError> {
+        let (payload, signature) = session.split_once('.').ok_or(SessionError::Malformed)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| SessionError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| SessionError::Malformed)?;
+        let expires_at: [u8; 8] = payload
+            .as_slice()
+            .try_into()
+            .map_err(|_| SessionError::Malformed)?;
+
+        // Constant-time comparison via the MAC implementation
+        self.mac(&payload)
+            .verify_slice(&signature)
+            .map_err(|_| SessionError::BadSignature)?;
+
+        if now.timestamp() > i64::from_be_bytes(expires_at) {
+            return Err(SessionError::Expired);
+        }
+        Ok(())
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(SESSION_CONTEXT);
+        mac.update(payload);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_expires_and_hides_the_secret() {
+        let signer = SessionSigner::new("admin-token");
+        let now = Utc::now();
+        let session = signer.sign(now + SESSION_TTL);
+
+        assert!(!session.contains("admin-token"));
+        assert_eq!(signer.verify(&session, now), Ok(()));
+        assert_eq!(
+            signer.verify(&session, now + SESSION_TTL + Duration::seconds(1)),
+            Err(SessionError::Expired)
+        );
+    }
+
+    #[test]
+    fn test_rejects_forged_and_rotated_sessions() {
+        let signer = SessionSigner::new("admin-token");
+        let now = Utc::now();
+        let session = signer.sign(now + SESSION_TTL);
+
+        let rotated = SessionSigner::new("new-admin-token");
+        assert_eq!(
+            rotated.verify(&session, now),
+            Err(SessionError::BadSignature)
+        );
+
+        let extended = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode((now + Duration::days(365)).timestamp().to_be_bytes()),
+            session.split_once('.').unwrap().1
+        );
+        assert_eq!(
+            signer.verify(&extended, now),
+            Err(SessionError::BadSignature)
+        );
+        assert_eq!(
+            signer.verify("admin-token", now),
+            Err(SessionError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,156 @@
+// XML sitemap generation
+//
+// URLs come from the HTML pages registered on the router plus every published
+// post, tag page and project page. Once the URL count passes the protocol
+// limit the sitemap is split into numbered pages behind a sitemap index.
+
+use crate::models::{BlogPost, CVData};
+use crate::utils::{slugify, xml_escape};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::collections::BTreeMap;
+
+/// Protocol limit on URLs per sitemap file
+pub const MAX_URLS_PER_SITEMAP: usize = 50_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapUrl {
+    pub path: String,
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+/// Parameterless HTML routes, recorded as they are registered on the router
+#[derive(Debug, Clone, Default)]
+pub struct SitemapPages {
+    paths: Vec<String>,
+}
+
+impl SitemapPages {
+    pub fn new(paths: Vec<String>) -> Self {
+        Self { paths }
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+}
+
+/// Every URL that belongs in the sitemap. `posts` must already be filtered
+/// to published posts.
+pub fn collect_urls(pages: &SitemapPages, posts: &[BlogPost], cv_data: &CVData) -> Vec<SitemapUrl> {
+    let mut urls: Vec<SitemapUrl> = pages
+        .paths()
+        .iter()
+        .map(|path| SitemapUrl {
+            path: path.clone(),
+            lastmod: None,
+        })
+        .collect();
+
+    let mut tags: BTreeMap<String, DateTime<Utc>> = BTreeMap::new();
+    for post in posts {
+        urls.push(SitemapUrl {
+            path: format!("/blog/{}", post.slug),
+            lastmod: Some(post.updated_at),
+        });
+        for tag in &post.tags {
+            let updated = tags.entry(slugify(tag)).or_insert(post.updated_at);
+            *updated = (*updated).max(post.updated_at);
+        }
+    }
+    urls.extend(tags.into_iter().map(|(tag, updated)| SitemapUrl {
+        path: format!("/blog/tag/{}", tag),
+        lastmod: Some(updated),
+    }));
+
+    urls.extend(cv_data.projects.iter().map(|project| SitemapUrl {
+        path: format!("/projects/{}", slugify(&project.name)),
+        lastmod: None,
+    }));
+
+    urls
+}
+
+/// Number of sitemap files needed for `url_count` URLs
+pub fn page_count(url_count: usize) -> usize {
+    url_count.div_ceil(MAX_URLS_PER_SITEMAP).max(1)
+}
+
+/// URLs on 1-based sitemap page `page`, if it exists
+pub fn page(urls: &[SitemapUrl], page: usize) -> Option<&[SitemapUrl]> {
+    if page == 0 || page > page_count(urls.len()) {
+        return None;
+    }
+    let start = (page - 1) * MAX_URLS_PER_SITEMAP;
+    Some(&urls[start..(start + MAX_URLS_PER_SITEMAP).min(urls.len())])
+}
+
+pub fn render_urlset(site_url: &str, urls: &[SitemapUrl]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for url in urls {
+        out.push_str("<url><loc>");
+        out.push_str(&xml_escape(&format!("{}{}", site_url, url.path)));
+        out.push_str("</loc>");
+        if let Some(lastmod) = url.lastmod {
+            out.push_str(&format!("<lastmod>{}</lastmod>", format_lastmod(lastmod)));
+        }
+        out.push_str("</url>\n");
+    }
+    out.push_str("</urlset>\n");
+    out
+}
+
+/// Sitemap index pointing at `/sitemaps/{n}.xml` for every page
+pub fn render_index(site_url: &str, urls: &[SitemapUrl]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for number in 1..=page_count(urls.len()) {
+        out.push_str(&format!(
+            "<sitemap><loc>{}</loc>",
+            xml_escape(&format!("{}/sitemaps/{}.xml", site_url, number))
+        ));
+        let lastmod = page(urls, number)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|url| url.lastmod)
+            .max();
+        if let Some(lastmod) = lastmod {
+            out.push_str(&format!("<lastmod>{}</lastmod>", format_lastmod(lastmod)));
+        }
+        out.push_str("</sitemap>\n");
+    }
+    out.push_str("</sitemapindex>\n");
+    out
+}
+
+fn format_lastmod(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_sitemaps_are_split_into_pages() {
+        let urls: Vec<SitemapUrl> = (0..MAX_URLS_PER_SITEMAP + 1)
+            .map(|n| SitemapUrl {
+                path: format!("/blog/post-{}", n),
+                lastmod: None,
+            })
+            .collect();
+
+        assert_eq!(page_count(urls.len()), 2);
+        assert_eq!(page(&urls, 1).unwrap().len(), MAX_URLS_PER_SITEMAP);
+        assert_eq!(page(&urls, 2).unwrap().len(), 1);
+        assert!(page(&urls, 3).is_none());
+
+        let index = render_index("https://example.com", &urls);
+        assert!(index.contains("<loc>https://example.com/sitemaps/2.xml</loc>"));
+        assert_eq!(page_count(0), 1);
+    }
+}
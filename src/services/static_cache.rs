@@ -0,0 +1,269 @@
+// In-memory static file cache
+//
+// Keeps recently served static files in memory together with their compressed
+// variants and a strong content ETag, bounded by total bytes in LRU order.
+// Entries are checked against the file's size and modification time on every
+// lookup, so edits on disk are picked up on the next request.
+
+use crate::services::precompress::{self, Encoding, Level};
+use anyhow::Result;
+use axum::body::Bytes;
+use lru::LruCache;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// A cached file and its precompressed variants
+pub struct CachedFile {
+    pub identity: Bytes,
+    brotli: Option<Bytes>,
+    gzip: Option<Bytes>,
+    /// Strong ETag of the identity content, without quotes
+    digest: String,
+    pub modified: Option<SystemTime>,
+    source_len: u64,
+}
+
+impl CachedFile {
+    /// The first of `accepted` encodings with a variant, else identity
+    pub fn variant(&self, accepted: &[Encoding]) -> (Bytes, Option<Encoding>) {
+        for &encoding in accepted {
+            let variant = match encoding {
+                Encoding::Brotli => &self.brotli,
+                Encoding::Gzip => &self.gzip,
+            };
+            if let Some(bytes) = variant {
+                return (bytes.clone(), Some(encoding));
+            }
+        }
+        (self.identity.clone(), None)
+    }
+
+    /// Quoted strong ETag for the given variant
+    pub fn etag(&self, encoding: Option<Encoding>) -> String {
+        match encoding {
+            Some(encoding) => format!("\"{}-{}\"", self.digest, encoding.extension()),
+            None => format!("\"{}\"", self.digest),
+        }
+    }
+
+    fn size(&self) -> u64 {
+        (self.identity.len()
+            + self.brotli.as_ref().map_or(0, Bytes::len)
+            + self.gzip.as_ref().map_or(0, Bytes::len)) as u64
+    }
+
+    fn is_current(&self, metadata: &Metadata) -> bool {
+        self.source_len == metadata.len() && self.modified == metadata.modified().ok()
+    }
+}
+
+/// Point-in-time cache counters, as served on `/metrics`
+#[derive(Debug, Clone, Serialize)]
+pub struct StaticCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+}
+
+impl StaticCacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+struct Entries {
+    files: LruCache<PathBuf, Arc<CachedFile>>,
+    bytes: u64,
+}
+
+pub struct StaticCache {
+    entries: Mutex<Entries>,
+    max_bytes: u64,
+    max_file_bytes: u64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl StaticCache {
+    /// Cache holding up to `max_bytes` in total (variants included), for
+    /// files up to `max_file_bytes`. Either limit at 0 disables caching.
+    pub fn new(max_bytes: u64, max_file_bytes: u64) -> Self {
+        Self {
+            entries: Mutex::new(Entries {
+                files: LruCache::unbounded(),
+                bytes: 0,
+            }),
+            max_bytes,
+            max_file_bytes: max_file_bytes.min(max_bytes),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether a file of `len` bytes is served from the cache
+    pub fn admits(&self, len: u64) -> bool {
+        len <= self.max_file_bytes
+    }
+
+    /// The cached file at `path`, loading it if missing or changed on disk.
+    /// `metadata` is the caller's fresh `stat` of `path`.
+    pub async fn get(&self, path: &Path, metadata: &Metadata) -> Result<Arc<CachedFile>> {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.files.get(path) {
+                Some(cached) if cached.is_current(metadata) => {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return Ok(cached.clone());
+                }
+                Some(_) => {
+                    if let Some(stale) = entries.files.pop(path) {
+                        entries.bytes -= stale.size();
+                    }
+                    self.invalidations.fetch_add(1, Ordering::Relaxed);
+                }
+                None => {}
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let owned = path.to_path_buf();
+        let loaded = Arc::new(tokio::task::spawn_blocking(move || load(&owned)).await??);
+        self.insert(path, loaded.clone());
+        Ok(loaded)
+    }
+
+    pub fn stats(&self) -> StaticCacheStats {
+        let entries = self.entries.lock().unwrap();
+        StaticCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: entries.files.len(),
+            bytes: entries.bytes,
+            max_bytes: self.max_bytes,
+        }
+    }
+
+    fn insert(&self, path: &Path, file: Arc<CachedFile>) {
+        let size = file.size();
+        if size > self.max_bytes {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(previous) = entries.files.put(path.to_path_buf(), file) {
+            entries.bytes -= previous.size();
+        }
+        entries.bytes += size;
+        while entries.bytes > self.max_bytes {
+            let Some((_, evicted)) = entries.files.pop_lru() else {
+                break;
+            };
+            entries.bytes -= evicted.size();
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Read `path` and prepare its variants: sidecars when present and current,
+/// otherwise compressed here for compressible types
+fn load(path: &Path) -> Result<CachedFile> {
+    let metadata = std::fs::metadata(path)?;
+    let identity = std::fs::read(path)?;
+    let modified = metadata.modified().ok();
+
+    let variant = |encoding: Encoding| -> Result<Option<Bytes>> {
+        let sidecar = precompress::sidecar_path(path, encoding);
+        let sidecar_current = std::fs::metadata(&sidecar)
+            .and_then(|sidecar| sidecar.modified())
+            .is_ok_and(|sidecar_modified| {
+                modified.is_some_and(|modified| sidecar_modified >= modified)
+            });
+        if sidecar_current {
+            return Ok(Some(std::fs::read(&sidecar)?.into()));
+        }
+        if !precompress::is_compressible(path) {
+            return Ok(None);
+        }
+        let compressed = precompress::compress(&identity, encoding, Level::Balanced)?;
+        Ok((compressed.len() < identity.len()).then(|| compressed.into()))
+    };
+
+    Ok(CachedFile {
+        brotli: variant(Encoding::Brotli)?,
+        gzip: variant(Encoding::Gzip)?,
+        digest: hex::encode(&Sha256::digest(&identity)[..16]),
+        modified,
+        source_len: metadata.len(),
+        identity: identity.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cache_hits_invalidates_and_evicts() {
+        let dir = std::env::temp_dir().join(format!("static-cache-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let css = dir.join("site.css");
+        let other = dir.join("other.css");
+        std::fs::write(&css, "body { color: red; }\n".repeat(200)).unwrap();
+        std::fs::write(&other, "p { margin: 0; }\n".repeat(200)).unwrap();
+
+        let cache = StaticCache::new(6000, 5000);
+        let first = cache
+            .get(&css, &std::fs::metadata(&css).unwrap())
+            .await
+            .unwrap();
+        let (_, encoding) = first.variant(&[Encoding::Brotli, Encoding::Gzip]);
+        assert_eq!(encoding, Some(Encoding::Brotli));
+        assert_ne!(first.etag(None), first.etag(encoding));
+
+        cache
+            .get(&css, &std::fs::metadata(&css).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(cache.stats().hits, 1);
+
+        // A change on disk replaces the entry
+        std::fs::write(&css, "body { color: blue; }\n".repeat(150)).unwrap();
+        let changed = cache
+            .get(&css, &std::fs::metadata(&css).unwrap())
+            .await
+            .unwrap();
+        assert_ne!(changed.etag(None), first.etag(None));
+        assert_eq!(cache.stats().invalidations, 1);
+
+        // Loading a second file pushes the first out of the byte budget
+        cache
+            .get(&other, &std::fs::metadata(&other).unwrap())
+            .await
+            .unwrap();
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 1);
+        assert!(stats.bytes <= stats.max_bytes);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
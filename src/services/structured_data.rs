@@ -0,0 +1,330 @@
+// schema.org JSON-LD structured data
+//
+// Built from the CV and blog models and emitted into templates through the
+// `structured_data` Tera function, so search engines see the same facts the
+// page renders.
+
+use crate::models::{BlogPost, CVData, Education, Experience};
+use crate::services::og_images::{post_card_path, OgCard};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const CONTEXT: &str = "https://schema.org";
+
+/// Profiles that identify the same person elsewhere
+const SAME_AS: &[&str] = &[
+    "https://github.com/dav88dev",
+    "https://www.linkedin.com/in/dav88dev",
+];
+
+/// `Person` for the site owner, with education and work history
+pub fn person(site_url: &str, cv_data: &CVData) -> Value {
+    let info = &cv_data.personal_info;
+    let mut person = json!({
+        "@context": CONTEXT,
+        "@type": "Person",
+        "@id": person_id(site_url),
+        "name": info.name,
+        "jobTitle": info.title,
+        "description": info.summary,
+        "url": format!("{}/", site_url),
+        "email": format!("mailto:{}", info.email),
+        "homeLocation": { "@type": "Place", "name": info.location },
+        "sameAs": SAME_AS,
+        "alumniOf": cv_data.education.iter().map(alumni_of).collect::<Vec<_>>(),
+        "hasOccupation": cv_data.experience.iter().map(occupation).collect::<Vec<_>>(),
+        "knowsAbout": cv_data.skills.iter().map(|skill| &skill.name).collect::<Vec<_>>(),
+    });
+
+    let current: Vec<Value> = cv_data
+        .experience
+        .iter()
+        .filter(|job| is_current(job))
+        .map(|job| organization(&job.company))
+        .collect();
+    if !current.is_empty() {
+        person["worksFor"] = Value::Array(current);
+    }
+    person
+}
+
+/// `BlogPosting` for a post, authored by the site owner
+pub fn blog_posting(site_url: &str, post: &BlogPost, cv_data: &CVData) -> Value {
+    let url = format!("{}/blog/{}", site_url, post.slug);
+    let author = &cv_data.personal_info.name;
+    let mut posting = json!({
+        "@context": CONTEXT,
+        "@type": "BlogPosting",
+        "headline": post.title,
+        "url": url,
+        "mainEntityOfPage": { "@type": "WebPage", "@id": url },
+        "datePublished": post.published_at().to_rfc3339(),
+        "dateModified": post.updated_at.to_rfc3339(),
+        "author": { "@type": "Person", "@id": person_id(site_url), "name": author },
+        "image": format!(
+            "{}{}?v={}",
+            site_url,
+            post_card_path(&post.slug),
+            OgCard::for_post(post, author).hash()
+        ),
+        "keywords": post.tags,
+    });
+    if let Some(excerpt) = &post.excerpt {
+        posting["description"] = json!(excerpt);
+    }
+    posting
+}
+
+/// `BreadcrumbList` starting at the home page. Crumbs without a path (the
+/// current page, typically) are listed without an `item` URL.
+pub fn breadcrumbs(site_url: &str, crumbs: &[(String, Option<String>)]) -> Value {
+    let home = ("Home".to_string(), Some("/".to_string()));
+    let items: Vec<Value> = std::iter::once(&home)
+        .chain(crumbs)
+        .enumerate()
+        .map(|(index, (name, path))| {
+            let mut item = json!({
+                "@type": "ListItem",
+                "position": index + 1,
+                "name": name,
+            });
+            if let Some(path) = path {
+                item["item"] = json!(format!("{}{}", site_url, path));
+            }
+            item
+        })
+        .collect();
+
+    json!({
+        "@context": CONTEXT,
+        "@type": "BreadcrumbList",
+        "itemListElement": items,
+    })
+}
+
+/// Wrap JSON-LD in a script element. `<` is escaped so content can never
+/// close the element early.
+pub fn script_tag(data: &Value) -> String {
+    format!(
+        "<script type=\"application/ld+json\">{}</script>",
+        data.to_string().replace('<', "\\u003c")
+    )
+}
+
+fn person_id(site_url: &str) -> String {
+    format!("{}/#person", site_url)
+}
+
+fn alumni_of(education: &Education) -> Value {
+    json!({
+        "@type": "EducationalOrganization",
+        "name": education.institution,
+        "description": education.degree,
+    })
+}
+
+fn occupation(job: &Experience) -> Value {
+    json!({
+        "@type": "Occupation",
+        "name": job.title,
+        "description": job.description.join(" "),
+        "hiringOrganization": organization(&job.company),
+    })
+}
+
+/// Company names in the CV carry a location suffix, e.g. `Acme (Remote)`
+fn organization(company: &str) -> Value {
+    let name = company.split(" (").next().unwrap_or(company).trim();
+    json!({ "@type": "Organization", "name": name })
+}
+
+fn is_current(job: &Experience) -> bool {
+    let end = job.duration.rsplit(['–', '-']).next().unwrap_or("").trim();
+    end.eq_ignore_ascii_case("current") || end.eq_ignore_ascii_case("present")
+}
+
+/// Tera function `structured_data(type=...)` rendering a JSON-LD script:
+///
+/// - `type="Person"`: the site owner
+/// - `type="BlogPosting", post=post`
+/// - `type="BreadcrumbList", names=[...], paths=[...]`: crumbs after Home;
+///   `paths` may be shorter than `names` to leave the last crumbs unlinked
+pub struct StructuredDataFunction {
+    site_url: String,
+    cv_data: Arc<CVData>,
+}
+
+impl StructuredDataFunction {
+    pub fn new(site_url: impl Into<String>, cv_data: Arc<CVData>) -> Self {
+        Self {
+            site_url: site_url.into(),
+            cv_data,
+        }
+    }
+}
+
+impl tera::Function for StructuredDataFunction {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let kind = args
+            .get("type")
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| tera::Error::msg("`structured_data` requires a `type` argument"))?;
+
+        let data = match kind {
+            "Person" => person(&self.site_url, &self.cv_data),
+            "BlogPosting" => {
+                let post: BlogPost = args
+                    .get("post")
+                    .cloned()
+                    .map(serde_json::from_value)
+                    .transpose()
+                    .map_err(|err| tera::Error::msg(format!("invalid `post`: {}", err)))?
+                    .ok_or_else(|| tera::Error::msg("`BlogPosting` requires a `post` argument"))?;
+                blog_posting(&self.site_url, &post, &self.cv_data)
+            }
+            "BreadcrumbList" => {
+                let names: Vec<String> = string_list(args, "names")?;
+                let paths: Vec<String> = string_list(args, "paths")?;
+                let crumbs: Vec<(String, Option<String>)> = names
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, name)| (name, paths.get(index).cloned()))
+                    .collect();
+                breadcrumbs(&self.site_url, &crumbs)
+            }
+            other => {
+                return Err(tera::Error::msg(format!(
+                    "`structured_data` does not support type `{}`",
+                    other
+                )))
+            }
+        };
+
+        Ok(tera::Value::String(script_tag(&data)))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+fn string_list(args: &HashMap<String, tera::Value>, name: &str) -> tera::Result<Vec<String>> {
+    args.get(name)
+        .cloned()
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|err| tera::Error::msg(format!("invalid `{}`: {}", name, err)))
+        .map(Option::unwrap_or_default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    const SITE: &str = "https://example.com";
+
+    fn post() -> BlogPost {
+        BlogPost {
+            id: Uuid::new_v4(),
+            title: "Hello </script> world".to_string(),
+            slug: "hello-world".to_string(),
+            content: "Body".to_string(),
+            excerpt: Some("Short".to_string()),
+            published: true,
+            publish_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            tags: vec!["rust".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_person_is_built_from_cv_data() {
+        let cv_data = CVData::default();
+        let person = person(SITE, &cv_data);
+
+        assert_eq!(person["@context"], CONTEXT);
+        assert_eq!(person["@type"], "Person");
+        assert_eq!(person["name"], cv_data.personal_info.name);
+        assert_eq!(
+            person["alumniOf"].as_array().unwrap().len(),
+            cv_data.education.len()
+        );
+        assert!(person["alumniOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|school| school["@type"] == "EducationalOrganization"));
+        assert!(person["hasOccupation"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|job| job["@type"] == "Occupation" && job["name"].is_string()));
+
+        // Only the current job, without the location suffix
+        let works_for = person["worksFor"].as_array().unwrap();
+        assert_eq!(works_for.len(), 1);
+        assert_eq!(works_for[0]["@type"], "Organization");
+        assert_eq!(works_for[0]["name"], "LenderHomePage.com");
+    }
+
+    #[test]
+    fn test_blog_posting_and_breadcrumbs() {
+        let cv_data = CVData::default();
+        let posting = blog_posting(SITE, &post(), &cv_data);
+        assert_eq!(posting["@type"], "BlogPosting");
+        assert_eq!(posting["url"], "https://example.com/blog/hello-world");
+        assert_eq!(posting["author"]["@type"], "Person");
+        assert_eq!(posting["description"], "Short");
+        assert!(posting["datePublished"].is_string());
+
+        let trail = breadcrumbs(
+            SITE,
+            &[
+                ("Blog".to_string(), Some("/blog".to_string())),
+                ("Hello".to_string(), None),
+            ],
+        );
+        assert_eq!(trail["@type"], "BreadcrumbList");
+        let items = trail["itemListElement"].as_array().unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0]["item"], "https://example.com/");
+        assert_eq!(items[1]["position"], 2);
+        assert!(items[2].get("item").is_none());
+    }
+
+    #[test]
+    fn test_tera_function_renders_escaped_script() {
+        let mut tera = tera::Tera::default();
+        tera.register_function(
+            "structured_data",
+            StructuredDataFunction::new(SITE, Arc::new(CVData::default())),
+        );
+        tera.add_raw_template(
+            "page",
+            r#"{{ structured_data(type="BlogPosting", post=post) }}{{ structured_data(type="BreadcrumbList", names=["Blog", post.title], paths=["/blog"]) }}"#,
+        )
+        .unwrap();
+
+        let mut context = tera::Context::new();
+        context.insert("post", &post());
+        let html = tera.render("page", &context).unwrap();
+
+        assert_eq!(
+            html.matches("<script type=\"application/ld+json\">")
+                .count(),
+            2
+        );
+        assert!(!html.contains("</script> world"));
+
+        let first = html
+            .strip_prefix("<script type=\"application/ld+json\">")
+            .and_then(|rest| rest.split("</script>").next())
+            .unwrap();
+        let posting: Value = serde_json::from_str(first).unwrap();
+        assert_eq!(posting["headline"], "Hello </script> world");
+    }
+}
@@ -1,24 +1,255 @@
 use crate::config::AppConfig;
+use crate::models::CVData;
+use crate::services::assets::{read_manifest, MANIFEST_PATH};
+use crate::services::embedded::AssetSource;
+use crate::services::filters::{register_asset_url, register_site_filters};
+use crate::services::markdown::MarkdownRenderer;
+use crate::services::structured_data::StructuredDataFunction;
+use crate::services::vite::{ViteClientFunction, ViteEntryFunction};
 use anyhow::Result;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
 use tera::Tera;
+use walkdir::WalkDir;
 
-pub fn create_template_engine(config: &AppConfig) -> Result<Arc<Tera>> {
+/// How often the templates directory is checked for changes in development
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub fn create_template_engine(
+    config: &AppConfig,
+    markdown: Arc<MarkdownRenderer>,
+    cv_data: Arc<CVData>,
+) -> Result<Arc<Tera>> {
+    let source = AssetSource::new(config);
     let template_glob = format!("{}/**/*.tera", config.templates_dir);
 
-    let mut tera = match Tera::new(&template_glob) {
-        Ok(t) => t,
-        Err(e) => {
-            tracing::error!("Template parsing error: {}", e);
-            return Err(e.into());
+    let mut tera = if source.reads_disk() {
+        match Tera::new(&template_glob) {
+            Ok(t) => t,
+            Err(e) => {
+                tracing::error!("Template parsing error: {}", e);
+                return Err(e.into());
+            }
         }
+    } else {
+        Tera::default()
     };
 
-    // Enable auto-reload in development
+    add_embedded_templates(&mut tera, &source)?;
+
+    // Site-specific filters and functions
+    register_site_filters(&mut tera, markdown);
+    register_manifest_functions(&mut tera, config, &source)?;
+    tera.register_function(
+        "structured_data",
+        StructuredDataFunction::new(&config.site_url, cv_data),
+    );
+    tera.register_function(
+        "vite_client",
+        ViteClientFunction::new(config.vite_dev_server().map(str::to_string)),
+    );
+
     if config.is_development() {
         tera.autoescape_on(vec![".html", ".htm", ".xml"]);
-        tracing::info!("Template auto-reload enabled for development");
     }
 
     Ok(Arc::new(tera))
 }
+
+/// `asset_url` and `vite_entry`, resolving entries through the Vite manifest
+/// as it is on disk now
+fn register_manifest_functions(
+    tera: &mut Tera,
+    config: &AppConfig,
+    source: &AssetSource,
+) -> tera::Result<()> {
+    let manifest = read_manifest(source)
+        .map_err(|e| tera::Error::msg(format!("Failed to read the Vite manifest: {:#}", e)))?;
+    let vite_dev_server = config.vite_dev_server().map(str::to_string);
+    register_asset_url(tera, manifest.clone(), vite_dev_server.clone());
+    tera.register_function(
+        "vite_entry",
+        ViteEntryFunction::new(manifest, vite_dev_server, config.is_development()),
+    );
+    Ok(())
+}
+
+/// Embedded templates fill in whatever the disk didn't provide
+fn add_embedded_templates(tera: &mut Tera, source: &AssetSource) -> tera::Result<()> {
+    if source.embedded_templates().is_empty() {
+        return Ok(());
+    }
+    let mut embedded = Tera::default();
+    embedded.add_raw_templates(source.embedded_templates().to_vec())?;
+    tera.extend(&embedded)?;
+    tera.build_inheritance_chains()
+}
+
+/// The template engine handlers render with, swapped as a whole when the
+/// templates are reloaded
+pub struct SharedTemplates {
+    current: RwLock<Arc<Tera>>,
+    /// Bumped with every successful reload, under the `current` write lock
+    version: AtomicU64,
+    /// Why the last reload failed, until a reload succeeds
+    error: RwLock<Option<String>>,
+}
+
+impl SharedTemplates {
+    pub fn new(tera: Arc<Tera>) -> Self {
+        Self {
+            current: RwLock::new(tera),
+            version: AtomicU64::new(0),
+            error: RwLock::new(None),
+        }
+    }
+
+    pub fn current(&self) -> Arc<Tera> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// The current engine with its version, for caching rendered output
+    pub fn snapshot(&self) -> (Arc<Tera>, u64) {
+        let current = self.current.read().unwrap();
+        (current.clone(), self.version.load(Ordering::Relaxed))
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.error.read().unwrap().clone()
+    }
+
+    /// Reload every template from disk, and the Vite manifest behind
+    /// `asset_url` and `vite_entry`. A broken template or manifest keeps the
+    /// last good engine in place and records the error instead.
+    pub fn reload(&self, config: &AppConfig, source: &AssetSource) {
+        let mut tera = Tera::clone(&self.current());
+        let reloaded = tera
+            .full_reload()
+            .and_then(|()| add_embedded_templates(&mut tera, source))
+            .and_then(|()| register_manifest_functions(&mut tera, config, source));
+
+        match reloaded {
+            Ok(()) => {
+                let mut current = self.current.write().unwrap();
+                *current = Arc::new(tera);
+                self.version.fetch_add(1, Ordering::Relaxed);
+                drop(current);
+                *self.error.write().unwrap() = None;
+                tracing::info!("Templates reloaded");
+            }
+            Err(e) => {
+                let message = error_chain(&e);
+                tracing::error!(
+                    "Template reload failed, keeping previous templates: {}",
+                    message
+                );
+                *self.error.write().unwrap() = Some(message);
+            }
+        }
+    }
+}
+
+/// Tera's top-level errors are generic; the cause says what is wrong where
+fn error_chain(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message.push('\n');
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message.trim().to_string()
+}
+
+/// Reload templates whenever a file under the templates directory or the
+/// Vite manifest changes. Only templates read from disk can change, so this
+/// is for development.
+pub fn spawn_template_reloader(templates: Arc<SharedTemplates>, config: &AppConfig) {
+    let source = AssetSource::new(config);
+    if !source.reads_disk() {
+        return;
+    }
+    let watched = [
+        source.templates_dir().to_path_buf(),
+        source.static_dir().join(MANIFEST_PATH),
+    ];
+    let config = config.clone();
+    tracing::info!("Template auto-reload enabled for development");
+
+    tokio::spawn(async move {
+        let mut last = snapshot(&watched);
+        let mut interval = tokio::time::interval(RELOAD_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let current = snapshot(&watched);
+            if current != last {
+                last = current;
+                templates.reload(&config, &source);
+            }
+        }
+    });
+}
+
+/// Path, size and modification time of every file under `paths`
+fn snapshot(paths: &[PathBuf]) -> Vec<(PathBuf, u64, Option<SystemTime>)> {
+    let mut files: Vec<_> = paths
+        .iter()
+        .flat_map(WalkDir::new)
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some((entry.into_path(), metadata.len(), metadata.modified().ok()))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload_picks_up_a_rebuilt_manifest() {
+        let dir = std::env::temp_dir().join(format!("templates-{}", uuid::Uuid::new_v4()));
+        let templates_dir = dir.join("templates");
+        let static_dir = dir.join("static");
+        std::fs::create_dir_all(&templates_dir).unwrap();
+        std::fs::create_dir_all(static_dir.join(".vite")).unwrap();
+        std::fs::write(
+            templates_dir.join("page.html.tera"),
+            r#"{{ asset_url(path="js/main.js") }}"#,
+        )
+        .unwrap();
+        let write_manifest = |file: &str| {
+            let manifest = format!(r#"{{ "js/main.js": {{ "file": "{}" }} }}"#, file);
+            std::fs::write(static_dir.join(MANIFEST_PATH), manifest).unwrap();
+        };
+        write_manifest("js/main-AbC123xY.js");
+
+        let mut config = AppConfig::from_env();
+        config.templates_dir = templates_dir.to_string_lossy().into_owned();
+        config.static_dir = static_dir.to_string_lossy().into_owned();
+        let markdown = Arc::new(MarkdownRenderer::new());
+        let cv_data = Arc::new(CVData::default());
+        let templates =
+            SharedTemplates::new(create_template_engine(&config, markdown, cv_data).unwrap());
+        let render = || {
+            templates
+                .current()
+                .render("page.html.tera", &tera::Context::new())
+                .unwrap()
+        };
+        assert_eq!(render(), "/static/js/main-AbC123xY.js");
+
+        write_manifest("js/main-Zz9Yy8Xx.js");
+        templates.reload(&config, &AssetSource::new(&config));
+        assert!(templates.last_error().is_none());
+        assert_eq!(render(), "/static/js/main-Zz9Yy8Xx.js");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,317 @@
+// Vite integration: `vite_entry` template function and dev-server proxy
+//
+// Renders the tags for a Vite entry straight from the manifest, so a new entry
+// point only needs a template change: the entry's script (or stylesheet), the
+// CSS of the entry and every chunk it imports, and `modulepreload` hints for
+// those chunks so the browser fetches them in parallel with the entry.
+//
+// In development with a Vite dev server configured, entries point at the dev
+// server's unbundled modules instead, `vite_client` injects the HMR client and
+// static requests with no file on disk are proxied to Vite.
+
+use crate::services::assets::AssetInfo;
+use anyhow::Result;
+use axum::body::Body;
+use axum::http::{header, HeaderMap, Method, Request, Uri};
+use axum::response::Response;
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use hyper_util::rt::TokioExecutor;
+use std::collections::{HashMap, HashSet};
+
+/// Public path of the static root, as used in the Vite `base` option
+pub const BASE: &str = "/static/";
+
+/// Request headers not forwarded to the dev server (hop-by-hop, or tied to
+/// our own connection)
+const HOP_HEADERS: &[header::HeaderName] = &[
+    header::CONNECTION,
+    header::HOST,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+    header::PROXY_AUTHORIZATION,
+];
+
+pub struct ViteEntryFunction {
+    /// `None` when the frontend hasn't been built
+    manifest: Option<HashMap<String, AssetInfo>>,
+    /// Vite dev server URL; entries are then served unbundled from there
+    dev_server: Option<String>,
+    /// Unknown entries fail rendering instead of being logged and skipped
+    strict: bool,
+}
+
+impl ViteEntryFunction {
+    pub fn new(
+        manifest: Option<HashMap<String, AssetInfo>>,
+        dev_server: Option<String>,
+        strict: bool,
+    ) -> Self {
+        Self {
+            manifest,
+            dev_server,
+            strict,
+        }
+    }
+
+    /// Tags for manifest entry `name`, or `None` if there is no such entry.
+    /// From the dev server, or without a manifest, the unbundled source path
+    /// is referenced directly.
+    pub fn render(&self, name: &str) -> Option<String> {
+        if let Some(dev_server) = &self.dev_server {
+            return Some(entry_tag(&format!("{}{}", dev_server, BASE), name));
+        }
+        let Some(manifest) = &self.manifest else {
+            return Some(entry_tag(BASE, name));
+        };
+        let entry = manifest.get(name)?;
+        let EntryFiles {
+            mut modules,
+            stylesheets,
+        } = entry_files(manifest, name)?;
+        modules.retain(|file| *file != entry.file);
+
+        let mut html = String::new();
+        for file in stylesheets {
+            html.push_str(&format!(
+                "<link rel=\"stylesheet\" href=\"{}{}\">\n",
+                BASE, file
+            ));
+        }
+        for file in modules {
+            html.push_str(&format!(
+                "<link rel=\"modulepreload\" href=\"{}{}\">\n",
+                BASE, file
+            ));
+        }
+        if !entry.file.ends_with(".css") {
+            html.push_str(&entry_tag(BASE, &entry.file));
+        }
+        Some(html.trim_end().to_string())
+    }
+}
+
+/// Script or stylesheet tag for a single file under `base`
+fn entry_tag(base: &str, file: &str) -> String {
+    if file.ends_with(".css") {
+        format!("<link rel=\"stylesheet\" href=\"{}{}\">", base, file)
+    } else {
+        format!("<script type=\"module\" src=\"{}{}\"></script>", base, file)
+    }
+}
+
+/// Files a manifest entry needs, in dependency order
+pub struct EntryFiles<'a> {
+    /// JS chunks, the entry's own file last
+    pub modules: Vec<&'a str>,
+    /// CSS of the entry and every chunk it imports
+    pub stylesheets: Vec<&'a str>,
+}
+
+/// Walk manifest entry `name` and its static `imports`
+pub fn entry_files<'a>(
+    manifest: &'a HashMap<String, AssetInfo>,
+    name: &'a str,
+) -> Option<EntryFiles<'a>> {
+    manifest.get(name)?;
+    let mut files = EntryFiles {
+        modules: Vec::new(),
+        stylesheets: Vec::new(),
+    };
+    let mut visited = HashSet::new();
+    collect_chunk(
+        manifest,
+        name,
+        &mut visited,
+        &mut files.modules,
+        &mut files.stylesheets,
+    );
+    Some(files)
+}
+
+/// Depth-first walk of static `imports`, collecting chunk files and CSS in
+/// dependency order
+fn collect_chunk<'a>(
+    manifest: &'a HashMap<String, AssetInfo>,
+    key: &'a str,
+    visited: &mut HashSet<&'a str>,
+    modules: &mut Vec<&'a str>,
+    stylesheets: &mut Vec<&'a str>,
+) {
+    if !visited.insert(key) {
+        return;
+    }
+    let Some(chunk) = manifest.get(key) else {
+        return;
+    };
+    for import in chunk.imports.iter().flatten() {
+        collect_chunk(manifest, import, visited, modules, stylesheets);
+    }
+    if chunk.file.ends_with(".css") {
+        stylesheets.push(&chunk.file);
+    } else {
+        modules.push(&chunk.file);
+    }
+    for css in chunk.css.iter().flatten() {
+        if !stylesheets.contains(&css.as_str()) {
+            stylesheets.push(css);
+        }
+    }
+}
+
+impl tera::Function for ViteEntryFunction {
+    fn call(&self, args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let name = args
+            .get("name")
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| tera::Error::msg("`vite_entry` requires a `name` argument"))?;
+
+        match self.render(name) {
+            Some(html) => Ok(tera::Value::String(html)),
+            None if self.strict => Err(tera::Error::msg(format!(
+                "`{}` is not an entry in the Vite manifest",
+                name
+            ))),
+            None => {
+                tracing::error!("Vite manifest has no entry `{}`", name);
+                Ok(tera::Value::String(String::new()))
+            }
+        }
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// `vite_client()`: the HMR client script when a dev server is in use,
+/// otherwise nothing
+pub struct ViteClientFunction {
+    dev_server: Option<String>,
+}
+
+impl ViteClientFunction {
+    pub fn new(dev_server: Option<String>) -> Self {
+        Self { dev_server }
+    }
+}
+
+impl tera::Function for ViteClientFunction {
+    fn call(&self, _args: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+        let html = self
+            .dev_server
+            .as_ref()
+            .map(|dev_server| entry_tag(&format!("{}{}", dev_server, BASE), "@vite/client"))
+            .unwrap_or_default();
+        Ok(tera::Value::String(html))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Proxy to a running Vite dev server, for static requests with no file on
+/// disk (unbundled modules, `?import` and `?direct` variants, HMR updates)
+pub struct ViteDevServer {
+    url: String,
+    client: Client<HttpConnector, Body>,
+}
+
+impl ViteDevServer {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: Client::builder(TokioExecutor::new()).build_http(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Forward a GET for `uri` (path and query) and stream back the response
+    pub async fn proxy(&self, uri: &Uri, headers: &HeaderMap) -> Result<Response> {
+        let path = uri
+            .path_and_query()
+            .map_or(uri.path(), |path| path.as_str());
+        let mut request = Request::builder()
+            .method(Method::GET)
+            .uri(format!("{}{}", self.url, path))
+            .body(Body::empty())?;
+        for (name, value) in headers {
+            if !HOP_HEADERS.contains(name) {
+                request.headers_mut().append(name, value.clone());
+            }
+        }
+
+        let response = self.client.request(request).await?;
+        let (mut parts, body) = response.into_parts();
+        for name in HOP_HEADERS {
+            parts.headers.remove(name);
+        }
+        Ok(Response::from_parts(parts, Body::new(body)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_tags_include_imports_and_css() {
+        let manifest: HashMap<String, AssetInfo> = serde_json::from_str(
+            r#"{
+                "js/main.js": {
+                    "file": "js/main-AbC123xY.js",
+                    "isEntry": true,
+                    "imports": ["_core-Dq3kLm9x.js"],
+                    "dynamicImports": ["js/effects.js"],
+                    "css": ["css/main-Zz9Yy8Xx.css"]
+                },
+                "_core-Dq3kLm9x.js": {
+                    "file": "js/core-Dq3kLm9x.js",
+                    "imports": ["_vendor-Qq1Ww2Ee.js"],
+                    "css": ["css/core-Pp0Oo9Ii.css"]
+                },
+                "_vendor-Qq1Ww2Ee.js": { "file": "js/vendor-Qq1Ww2Ee.js" },
+                "js/effects.js": { "file": "js/effects-BDNgvF05.js" },
+                "css/style.css": { "file": "css/style-Dq3kLm9x.css", "isEntry": true }
+            }"#,
+        )
+        .unwrap();
+        let function = ViteEntryFunction::new(Some(manifest), None, true);
+
+        assert_eq!(
+            function.render("js/main.js").unwrap(),
+            [
+                r#"<link rel="stylesheet" href="/static/css/core-Pp0Oo9Ii.css">"#,
+                r#"<link rel="stylesheet" href="/static/css/main-Zz9Yy8Xx.css">"#,
+                r#"<link rel="modulepreload" href="/static/js/vendor-Qq1Ww2Ee.js">"#,
+                r#"<link rel="modulepreload" href="/static/js/core-Dq3kLm9x.js">"#,
+                r#"<script type="module" src="/static/js/main-AbC123xY.js"></script>"#,
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            function.render("css/style.css").unwrap(),
+            r#"<link rel="stylesheet" href="/static/css/style-Dq3kLm9x.css">"#
+        );
+
+        let args = HashMap::from([("name".to_string(), tera::Value::from("js/missing.js"))]);
+        assert!(tera::Function::call(&function, &args).is_err());
+        let lenient = ViteEntryFunction::new(function.manifest, None, false);
+        assert_eq!(
+            tera::Function::call(&lenient, &args).unwrap(),
+            tera::Value::from("")
+        );
+
+        // The dev server serves sources unbundled
+        let dev = ViteEntryFunction::new(None, Some("http://localhost:5173".to_string()), true);
+        assert_eq!(
+            dev.render("js/main.js").unwrap(),
+            r#"<script type="module" src="http://localhost:5173/static/js/main.js"></script>"#
+        );
+    }
+}
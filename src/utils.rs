@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 // Utility functions for the application
@@ -35,6 +36,64 @@ pub fn truncate_text(text: &str, max_length: usize) -> String {
     }
 }
 
+/// Format a timestamp as an HTTP-date (RFC 9110 IMF-fixdate)
+pub fn format_http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Parse an HTTP-date header value such as `If-Modified-Since`
+pub fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Escape text for use in XML element content and attribute values
+pub fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// The supported locale best matching an `Accept-Language` header. A range
+/// matches a locale exactly or by primary language (`en-GB` matches `en`);
+/// without a match the first supported locale is used.
+pub fn negotiate_locale<'a>(accept_language: Option<&str>, supported: &'a [String]) -> &'a str {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let range = parts.next()?.trim();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!range.is_empty() && quality > 0.0).then_some((range, quality))
+        })
+        .collect();
+    // Stable, so equally weighted ranges keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let primary = |tag: &str| tag.split('-').next().unwrap_or(tag).to_ascii_lowercase();
+    ranges
+        .iter()
+        .find_map(|(range, _)| {
+            supported
+                .iter()
+                .find(|locale| locale.eq_ignore_ascii_case(range))
+                .or_else(|| {
+                    supported
+                        .iter()
+                        .find(|locale| primary(locale) == primary(range))
+                })
+        })
+        .or(supported.first())
+        .map_or("en", String::as_str)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -51,4 +110,28 @@ mod tests {
         assert_eq!(truncate_text("Short", 10), "Short");
         assert_eq!(truncate_text("This is a longer text", 10), "This is...");
     }
+
+    #[test]
+    fn test_http_date_round_trip() {
+        let time = DateTime::parse_from_rfc3339("2025-03-09T08:05:01Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let formatted = format_http_date(time);
+
+        assert_eq!(formatted, "Sun, 09 Mar 2025 08:05:01 GMT");
+        assert_eq!(parse_http_date(&formatted), Some(time));
+    }
+
+    #[test]
+    fn test_negotiate_locale() {
+        let supported = vec!["en".to_string(), "de".to_string()];
+
+        assert_eq!(
+            negotiate_locale(Some("de-AT,de;q=0.9,en;q=0.8"), &supported),
+            "de"
+        );
+        assert_eq!(negotiate_locale(Some("fr, en-GB;q=0.5"), &supported), "en");
+        assert_eq!(negotiate_locale(Some("de;q=0, fr"), &supported), "en");
+        assert_eq!(negotiate_locale(None, &supported), "en");
+    }
 }